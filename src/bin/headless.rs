@@ -0,0 +1,101 @@
+use rts2::building_registry::{BuildingRegistry, BUILDING_TYPES_PATH};
+use rts2::entity_registry::{EntityRegistry, ENTITY_TYPES_PATH};
+use rts2::game::Game;
+use rts2::headless;
+use rts2::replay::{Replay, ReplayPlayer};
+use std::time::Instant;
+
+// Usage: headless [ticks] [seed] or headless --replay <file> [ticks]
+fn main() {
+    if std::env::args().nth(1).as_deref() == Some("--replay") {
+        run_replay();
+        return;
+    }
+
+    let ticks = match std::env::args().nth(1) {
+        Some(arg) => arg
+            .parse::<u32>()
+            .expect("ticks must be a positive integer"),
+        None => 60 * 60,
+    };
+    let seed = match std::env::args().nth(2) {
+        Some(arg) => arg.parse::<u64>().expect("seed must be a positive integer"),
+        None => 0,
+    };
+
+    let mut game = Game::new(
+        seed,
+        EntityRegistry::load_or_default(ENTITY_TYPES_PATH),
+        BuildingRegistry::load_or_default(BUILDING_TYPES_PATH),
+    );
+
+    let start_time = Instant::now();
+    headless::run(&mut game, ticks);
+    let total_time = start_time.elapsed();
+
+    println!(
+        "Ran {} ticks with seed {} in {}ms ({:.1} ticks/s)",
+        ticks,
+        seed,
+        total_time.as_millis(),
+        ticks as f32 / total_time.as_secs_f32()
+    );
+
+    print_summary(&game);
+}
+
+// Plays back a replay file, by default up to its last command
+fn run_replay() {
+    let path = std::env::args().nth(2).expect("--replay needs a file");
+    let replay = Replay::load(&path).unwrap();
+    let seed = replay.seed;
+
+    let mut replay_player = ReplayPlayer::new(replay);
+    let ticks = match std::env::args().nth(3) {
+        Some(arg) => arg
+            .parse::<u32>()
+            .expect("ticks must be a positive integer"),
+        None => replay_player.get_last_command_tick() + 1,
+    };
+
+    let start_time = Instant::now();
+    replay_player.seek(ticks);
+    let total_time = start_time.elapsed();
+
+    println!(
+        "Replayed {} ticks of {} with seed {} in {}ms",
+        ticks,
+        path,
+        seed,
+        total_time.as_millis()
+    );
+    print_summary(replay_player.get_game());
+}
+
+fn print_summary(game: &Game) {
+    for team in game.get_teams() {
+        let entity_count = game
+            .get_entity_container()
+            .iter_alive()
+            .filter(|entity| entity.borrow().get_team() == team.get_id())
+            .count();
+        let building_count = game
+            .get_building_container()
+            .get_buildings()
+            .iter()
+            .filter(|building| building.borrow().get_team() == team.get_id())
+            .count();
+        println!(
+            "Team {}: {} entities, {} buildings, {} gold, {} wood, {} stone, {}/{} supply",
+            team.get_id(),
+            entity_count,
+            building_count,
+            team.get_resources().gold,
+            team.get_resources().wood,
+            team.get_resources().stone,
+            team.get_supply_used(),
+            team.get_supply_cap()
+        );
+    }
+    println!("State checksum: {:016x}", game.state_checksum());
+}
@@ -1,80 +1,160 @@
-use crate::entity::EntityType;
+use crate::building_registry::{BuildingDefinition, BuildingType};
+use crate::constants::{CONSTRUCTION_START_HEALTH_PERCENT, REPAIR_AMOUNT, REPAIR_COST_PERCENT};
+use crate::entity::{AttackTarget, Entity};
+use crate::entity_registry::{EntityDefinition, ProjectileKind};
 use crate::event_handler::{Event, EventHandler};
+use crate::handle::Handle;
 use crate::health::Health;
+use crate::projectile::ProjectilePayload;
+use crate::resources::Resources;
 use crate::vec::{Vec2f, Vec2i};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
 
+#[derive(Serialize, Deserialize)]
 pub struct Building {
     position: Vec2i,
-    width: i32,
-    height: i32,
-    id: usize,
+    definition: Rc<BuildingDefinition>,
+    id: Handle,
     team: u8,
-    spawn_queue: Vec<EntityType>,
+    spawn_queue: Vec<Rc<EntityDefinition>>,
     spawn_timer: i32,
+    projectile_cooldown: i32,
     spawn_command_position: Option<Vec2f>,
     pub health: Health,
     pub construction_progress: Health,
+    // Paid for repairs but not used up yet, in 1 / (100 * health) of a resource. Repair steps
+    // cost less than a whole resource, so they are paid rounded up and the rest is kept here.
+    repair_credit: Resources,
 }
 
 impl Building {
     pub fn new(
         position: Vec2i,
-        width: i32,
-        height: i32,
         team: u8,
+        definition: Rc<BuildingDefinition>,
         is_constructed: bool,
     ) -> Building {
-        let random_id = rand::random::<usize>();
-
-        let mut spawn_queue = Vec::new();
+        let spawn_queue = Vec::new();
 
         Building {
             position,
-            width,
-            height,
-            id: random_id,
+            id: Handle::default(), // Set by BuildingContainer::add_building
             team,
             spawn_queue,
             spawn_timer: 0,
+            projectile_cooldown: 0,
             spawn_command_position: None,
-            health: Health::new(1000),
+            health: if is_constructed {
+                Health::new(definition.health)
+            } else {
+                Health::new(definition.health * CONSTRUCTION_START_HEALTH_PERCENT / 100)
+            },
             construction_progress: if is_constructed {
-                Health::new(100)
+                Health::new(definition.build_time)
             } else {
-                Health::new_with_health(0, 100)
+                Health::new_with_health(0, definition.build_time)
             },
+            repair_credit: Resources::new_empty(),
+            definition,
         }
     }
 
+    // Max health grows from the starting share to the full health as construction progresses,
+    // damage taken while under construction is kept
+    pub fn add_construction_progress(&mut self, amount: i32) {
+        self.construction_progress.increment(amount);
+
+        let start_health = self.definition.health * CONSTRUCTION_START_HEALTH_PERCENT / 100;
+        let max_health = start_health
+            + (self.definition.health - start_health) * self.construction_progress.get_health()
+                / self.definition.build_time.max(1);
+        self.health
+            .increase_max_health(max_health - self.health.get_max_health());
+    }
+
+    pub fn get_building_type(&self) -> BuildingType {
+        self.definition.building_type
+    }
+
+    pub fn get_definition(&self) -> &Rc<BuildingDefinition> {
+        &self.definition
+    }
+
     pub fn is_constructed(&self) -> bool {
         self.construction_progress.health_ratio() >= 1.0
     }
 
+    // A full repair costs REPAIR_COST_PERCENT of the building cost. What the next step costs in
+    // whole resources, after using up the credit of earlier steps.
+    pub fn get_repair_step_cost(&self) -> Resources {
+        let unit = 100 * self.definition.health;
+        let owed =
+            &(&self.definition.cost * (REPAIR_AMOUNT * REPAIR_COST_PERCENT)) - &self.repair_credit;
+        let round_up = |amount: i32| (amount.max(0) + unit - 1) / unit;
+        Resources::new(
+            round_up(owed.gold),
+            round_up(owed.wood),
+            round_up(owed.stone),
+        )
+    }
+
+    // `paid` is what get_repair_step_cost asked for
+    pub fn repair(&mut self, paid: &Resources) {
+        let unit = 100 * self.definition.health;
+        let step_cost = &self.definition.cost * (REPAIR_AMOUNT * REPAIR_COST_PERCENT);
+        self.repair_credit = &(&self.repair_credit + &(paid * unit)) - &step_cost;
+        self.health.increment(REPAIR_AMOUNT);
+    }
+
     pub fn get_spawn_duration(&self) -> i32 {
-        if let Some(entity_type) = self.spawn_queue.get(0) {
-            match entity_type {
-                EntityType::Worker => 50,
-                EntityType::Melee => 100,
-                EntityType::Ranged => 200,
-            }
+        if let Some(definition) = self.spawn_queue.first() {
+            definition.build_time
         } else {
             println!("This thing should not happen");
             0
         }
     }
 
-    pub fn add_to_spawn_queue(&mut self, entity_type: EntityType) {
-        self.spawn_queue.push(entity_type);
+    pub fn add_to_spawn_queue(&mut self, definition: Rc<EntityDefinition>) {
+        self.spawn_queue.push(definition);
+    }
+
+    // The removed item is returned so that it can be refunded
+    pub fn remove_from_spawn_queue(&mut self, index: usize) -> Option<Rc<EntityDefinition>> {
+        if index >= self.spawn_queue.len() {
+            return None;
+        }
+        if index == 0 {
+            // The item in production was cancelled, start the next one from scratch
+            self.spawn_timer = 0;
+        }
+        Some(self.spawn_queue.remove(index))
     }
 
-    pub fn get_spawn_queue(&self) -> &Vec<EntityType> {
+    pub fn get_spawn_queue(&self) -> &Vec<Rc<EntityDefinition>> {
         &self.spawn_queue
     }
 
     pub fn get_center_position(&self) -> Vec2f {
         Vec2f::new(
-            self.position.x as f32 + self.width as f32 / 2.0,
-            self.position.y as f32 + self.height as f32 / 2.0,
+            self.position.x as f32 + self.definition.width as f32 / 2.0,
+            self.position.y as f32 + self.definition.height as f32 / 2.0,
+        )
+    }
+
+    // Center of the tile of the building that is closest to the position, e.g. to aim at
+    pub fn get_closest_tile_center(&self, position: &Vec2f) -> Vec2f {
+        Vec2f::new(
+            position.x.clamp(
+                self.position.x as f32 + 0.5,
+                (self.position.x + self.definition.width) as f32 - 0.5,
+            ),
+            position.y.clamp(
+                self.position.y as f32 + 0.5,
+                (self.position.y + self.definition.height) as f32 - 0.5,
+            ),
         )
     }
 
@@ -82,10 +162,14 @@ impl Building {
         self.spawn_timer
     }
 
-    pub fn get_id(&self) -> usize {
+    pub fn get_id(&self) -> Handle {
         self.id
     }
 
+    pub fn set_id(&mut self, id: Handle) {
+        self.id = id;
+    }
+
     pub fn get_team(&self) -> u8 {
         self.team
     }
@@ -106,25 +190,91 @@ impl Building {
         self.position.clone()
     }
 
+    // Whether the tile is one of the building's own tiles
+    pub fn contains_tile(&self, tile: &Vec2i) -> bool {
+        tile.x >= self.position.x
+            && tile.x < self.position.x + self.get_width()
+            && tile.y >= self.position.y
+            && tile.y < self.position.y + self.get_height()
+    }
+
     pub fn get_width(&self) -> i32 {
-        self.width
+        self.definition.width
     }
 
     pub fn get_height(&self) -> i32 {
-        self.height
+        self.definition.height
+    }
+
+    pub fn update(
+        &mut self,
+        closest_enemy: Option<Rc<RefCell<Entity>>>,
+        is_supply_blocked: bool,
+        event_handler: &mut EventHandler,
+    ) {
+        if !self.is_constructed() {
+            return;
+        }
+
+        match self.definition.building_type {
+            BuildingType::TownHall | BuildingType::Barracks | BuildingType::ArcheryRange => {
+                self.update_spawn_queue(is_supply_blocked, event_handler)
+            }
+            BuildingType::Tower => self.update_tower(closest_enemy, event_handler),
+            BuildingType::Farm | BuildingType::Wall => {}
+        }
+    }
+
+    fn update_tower(
+        &mut self,
+        closest_enemy: Option<Rc<RefCell<Entity>>>,
+        event_handler: &mut EventHandler,
+    ) {
+        if self.projectile_cooldown > 0 {
+            self.projectile_cooldown -= 1;
+            return;
+        }
+        if let Some(closest_enemy) = closest_enemy {
+            let start = self.get_center_position();
+            let end = closest_enemy.borrow().get_position();
+            if (end.clone() - start.clone()).length() <= self.definition.attack_range {
+                self.projectile_cooldown = self.definition.cooldown;
+                event_handler.add_event(Event::AddRangedProjectile {
+                    start,
+                    end,
+                    kind: ProjectileKind::Arrow,
+                    payload: ProjectilePayload {
+                        damage: self.definition.damage,
+                        damage_type: self.definition.damage_type,
+                        splash_radius: 0.0,
+                        friendly_fire: false,
+                    },
+                    team: self.team,
+                    homing_target: if self.definition.homing {
+                        Some(AttackTarget::Entity(closest_enemy.borrow().get_id()))
+                    } else {
+                        None
+                    },
+                });
+            }
+        }
     }
 
-    pub fn update(&mut self, event_handler: &mut EventHandler) {
+    fn update_spawn_queue(&mut self, is_supply_blocked: bool, event_handler: &mut EventHandler) {
         if !self.spawn_queue.is_empty() {
+            if is_supply_blocked {
+                // Production is held until the team has the supply for what is queued
+                return;
+            }
             self.spawn_timer += 1;
 
             if self.spawn_timer >= self.get_spawn_duration() {
                 self.spawn_timer = 0;
 
-                let entity_type = self.spawn_queue.remove(0);
+                let definition = self.spawn_queue.remove(0);
 
                 event_handler.add_event(Event::SpawnEntity {
-                    entity_type,
+                    definition,
                     building_id: self.id,
                     team: self.team,
                 })
@@ -132,3 +282,29 @@ impl Building {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building_registry::BuildingRegistry;
+
+    #[test]
+    fn repairing_barracks_from_half_health_costs_resources() {
+        let registry = BuildingRegistry::default();
+        let definition = registry.get(BuildingType::Barracks).unwrap().clone();
+        let mut building = Building::new(Vec2i::new(0, 0), 0, definition.clone(), true);
+        building.health.take_damage(definition.health / 2);
+
+        let mut total_cost = Resources::new_empty();
+        while !building.health.is_full() {
+            let cost = building.get_repair_step_cost();
+            total_cost.increment(&cost);
+            building.repair(&cost);
+        }
+
+        // Half of the health at REPAIR_COST_PERCENT of the cost
+        let expected = &(&definition.cost * REPAIR_COST_PERCENT) / 200;
+        assert!(total_cost > Resources::new_empty());
+        assert_eq!(total_cost, expected);
+    }
+}
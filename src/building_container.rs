@@ -1,22 +1,54 @@
 use crate::building::Building;
-use crate::entity::EntityType;
+use crate::entity::EntityFilter;
+use crate::entity_container::EntityContainer;
+use crate::entity_registry::EntityDefinition;
 use crate::event_handler::EventHandler;
 use crate::ground::{Ground, GroundType};
+use crate::handle::{Handle, HandleStore};
+use crate::path_finder::distance_to_big_block;
+use crate::team::Team;
 use crate::vec::{Vec2f, Vec2i};
 use std::cell::RefCell;
 use std::rc::Rc;
 
 pub struct BuildingContainer {
     buildings: Vec<Rc<RefCell<Building>>>,
+    buildings_by_handle: HandleStore<Rc<RefCell<Building>>>,
+}
+
+impl Default for BuildingContainer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl BuildingContainer {
     pub fn new() -> BuildingContainer {
         BuildingContainer {
             buildings: Vec::new(),
+            buildings_by_handle: HandleStore::new(),
+        }
+    }
+
+    // Buildings that already have their ids and ground tiles set, e.g. from a save file
+    pub fn from_buildings(
+        buildings: Vec<Rc<RefCell<Building>>>,
+        mut buildings_by_handle: HandleStore<Rc<RefCell<Building>>>,
+    ) -> BuildingContainer {
+        for building in buildings.iter() {
+            let id = building.borrow().get_id();
+            buildings_by_handle.restore(id, building.clone());
+        }
+        BuildingContainer {
+            buildings,
+            buildings_by_handle,
         }
     }
 
+    pub fn get_handle_store(&self) -> &HandleStore<Rc<RefCell<Building>>> {
+        &self.buildings_by_handle
+    }
+
     pub fn add_building(
         &mut self,
         building: Building,
@@ -29,53 +61,105 @@ impl BuildingContainer {
         }
         // TODO: Set ground tiles to occupied
         let building_ref = Rc::new(RefCell::new(building));
+        let id = self.buildings_by_handle.insert(building_ref.clone());
+        building_ref.borrow_mut().set_id(id);
         self.buildings.push(building_ref.clone());
         building_ref
     }
 
-    // TODO: Remove building (set ground tiles to unoccupied)
+    fn clear_ground_tiles(building: &Building, ground: &mut Ground) {
+        for x in 0..building.get_width() {
+            for y in 0..building.get_height() {
+                ground.set_at(
+                    building.get_position().x + x,
+                    building.get_position().y + y,
+                    GroundType::Empty,
+                )
+            }
+        }
+    }
+
+    // Removes the building without it dying, e.g. when it is demolished
+    pub fn remove_building(
+        &mut self,
+        id: Handle,
+        ground: &mut Ground,
+    ) -> Option<Rc<RefCell<Building>>> {
+        let building_ref = self.buildings_by_handle.remove(id)?;
+        self.buildings
+            .retain(|building| !Rc::ptr_eq(building, &building_ref));
+        BuildingContainer::clear_ground_tiles(&building_ref.borrow(), ground);
+        Some(building_ref)
+    }
 
     pub fn get_buildings(&self) -> &Vec<Rc<RefCell<Building>>> {
         &self.buildings
     }
 
-    pub fn update_buildings(&mut self, event_handler: &mut EventHandler) {
+    pub fn update_buildings(
+        &mut self,
+        entity_container: &EntityContainer,
+        ground: &Ground,
+        teams: &[Team],
+        event_handler: &mut EventHandler,
+    ) {
         for building in self.buildings.iter_mut() {
-            building.borrow_mut().update(event_handler);
+            let is_supply_blocked = teams.iter().any(|team| {
+                team.get_id() == building.borrow().get_team() && team.is_supply_blocked()
+            });
+            let attack_range = building.borrow().get_definition().attack_range;
+            let team_id = building.borrow().get_team();
+            let visibility = teams
+                .iter()
+                .find(|team| team.get_id() == team_id)
+                .map(|team| team.get_visibility());
+            // Towers do not shoot through walls or into the fog of war
+            let closest_enemy = if attack_range > 0.0 {
+                let center = building.borrow().get_center_position();
+                entity_container.get_closest_entity_where(
+                    center.clone(),
+                    attack_range,
+                    EntityFilter::not_team(team_id),
+                    |entity| {
+                        visibility
+                            .is_some_and(|visibility| visibility.is_visible(&entity.get_position()))
+                            && ground.has_line_of_sight(&center, &entity.get_position())
+                    },
+                )
+            } else {
+                None
+            };
+            building
+                .borrow_mut()
+                .update(closest_enemy, is_supply_blocked, event_handler);
         }
     }
 
-    pub fn add_to_building_spawn_queue(&mut self, building_id: usize, entity_type: EntityType) {
-        for building in self.buildings.iter_mut() {
-            let this_building_id = building.borrow().get_id();
-            if this_building_id == building_id {
-                building.borrow_mut().add_to_spawn_queue(entity_type);
-                return;
-            }
+    pub fn add_to_building_spawn_queue(
+        &mut self,
+        building_id: Handle,
+        definition: Rc<EntityDefinition>,
+    ) {
+        if let Some(building) = self.buildings_by_handle.get(building_id) {
+            building.borrow_mut().add_to_spawn_queue(definition);
         }
     }
 
-    pub fn get_building_by_id(&self, id: usize) -> Option<Rc<RefCell<Building>>> {
-        for building in self.buildings.iter() {
-            if building.borrow().get_id() == id {
-                return Some(building.clone());
-            }
-        }
-        None
+    // None if the building has been destroyed
+    pub fn get_building_by_id(&self, id: Handle) -> Option<Rc<RefCell<Building>>> {
+        self.buildings_by_handle.get(id).cloned()
     }
 
-    pub fn set_spawn_command_position(&mut self, building_id: usize, pos: &Vec2f) {
-        for building_ref in self.buildings.iter_mut() {
-            let mut building = building_ref.borrow_mut();
-            if building.get_id() == building_id {
-                building.set_spawn_command_position(pos.clone());
-                return;
-            }
+    pub fn set_spawn_command_position(&mut self, building_id: Handle, pos: &Vec2f) {
+        match self.buildings_by_handle.get(building_id) {
+            Some(building) => building
+                .borrow_mut()
+                .set_spawn_command_position(pos.clone()),
+            None => println!(
+                "set_spawn_command_position no building found with id {:?}",
+                building_id
+            ),
         }
-        println!(
-            "set_spawn_command_position no building found with id {}",
-            building_id
-        );
     }
 
     pub fn get_closest_building(
@@ -84,12 +168,16 @@ impl BuildingContainer {
         team: Option<u8>,
         not_team: Option<u8>,
         max_distance: f32,
+        predicate: impl Fn(&Building) -> bool, // E.g. whether the building can be seen
     ) -> Option<Rc<RefCell<Building>>> {
         let mut closest_distance: f32 = 999999.0;
         let mut closest_building: Option<Rc<RefCell<Building>>> = None;
 
         for building_ref in self.buildings.iter() {
             let building = building_ref.borrow();
+            if !predicate(&building) {
+                continue;
+            }
             if let Some(not_team) = not_team {
                 if building.get_team() == not_team {
                     continue;
@@ -118,6 +206,84 @@ impl BuildingContainer {
         closest_building
     }
 
+    // Closest constructed building of the team that workers can bring resources to
+    pub fn get_closest_drop_off_building(
+        &self,
+        position: &Vec2i,
+        team: u8,
+    ) -> Option<Rc<RefCell<Building>>> {
+        let mut closest_distance: f32 = 999999.0;
+        let mut closest_building: Option<Rc<RefCell<Building>>> = None;
+
+        for building_ref in self.buildings.iter() {
+            let building = building_ref.borrow();
+            if building.get_team() != team
+                || !building.get_definition().is_drop_off
+                || !building.is_constructed()
+            {
+                continue;
+            }
+
+            let distance = (building.get_position() - position.clone())
+                .as_vec2f()
+                .length();
+            if distance < closest_distance {
+                closest_distance = distance;
+                closest_building = Some(building_ref.clone());
+            }
+        }
+
+        closest_building
+    }
+
+    // Buildings with any part within the radius, e.g. hit by a splash
+    pub fn get_buildings_in_radius(
+        &self,
+        position: &Vec2f,
+        radius: f32,
+        not_team: Option<u8>,
+    ) -> Vec<Rc<RefCell<Building>>> {
+        self.buildings
+            .iter()
+            .filter(|building_ref| {
+                let building = building_ref.borrow();
+                if not_team == Some(building.get_team()) {
+                    return false;
+                }
+                let size = Vec2i::new(building.get_width(), building.get_height());
+                distance_to_big_block(position, &building.get_position(), &size) <= radius
+            })
+            .cloned()
+            .collect()
+    }
+
+    // Unlike get_building_at only the building's own tiles count, not the tiles next to it
+    pub fn get_building_on_tile(
+        &self,
+        tile: &Vec2i,
+        team: Option<u8>,
+        not_team: Option<u8>,
+    ) -> Option<Rc<RefCell<Building>>> {
+        for building_ref in self.buildings.iter() {
+            let building = building_ref.borrow();
+            if !building.contains_tile(tile) {
+                continue;
+            }
+            if let Some(team) = team {
+                if building.get_team() != team {
+                    continue;
+                }
+            }
+            if let Some(not_team) = not_team {
+                if building.get_team() == not_team {
+                    continue;
+                }
+            }
+            return Some(building_ref.clone());
+        }
+        None
+    }
+
     pub fn get_building_at(
         &self,
         position: &Vec2i,
@@ -148,20 +314,14 @@ impl BuildingContainer {
     }
 
     pub fn remove_dead(&mut self, ground: &mut Ground) {
+        let buildings_by_handle = &mut self.buildings_by_handle;
         self.buildings.retain(|building| {
             let building = building.borrow();
             let is_alive = building.health.is_alive();
 
             if !is_alive {
-                for x in 0..building.get_width() {
-                    for y in 0..building.get_height() {
-                        ground.set_at(
-                            building.get_position().x + x,
-                            building.get_position().y + y,
-                            GroundType::Empty,
-                        )
-                    }
-                }
+                buildings_by_handle.remove(building.get_id());
+                BuildingContainer::clear_ground_tiles(&building, ground);
             }
 
             is_alive
@@ -0,0 +1,102 @@
+use crate::damage::{ArmorType, DamageType};
+use crate::entity_registry::EntityType;
+use crate::resources::Resources;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+pub const BUILDING_TYPES_PATH: &str = "assets/building_types.ron";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BuildingType {
+    TownHall,
+    Barracks,
+    ArcheryRange,
+    Farm,
+    Tower,
+    Wall,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct BuildingDefinition {
+    pub building_type: BuildingType,
+    pub width: i32,
+    pub height: i32,
+    pub cost: Resources,
+    pub build_time: i32, // Construction steps, a worker adds one every 30 ticks
+    pub health: i32,
+    pub armor: i32,
+    pub armor_type: ArmorType,
+    pub produces: Vec<EntityType>,
+    pub supply_provided: i32,
+    pub is_drop_off: bool, // Workers can bring gathered resources here
+    pub spacing: i32,      // Free tiles needed between this and other buildings
+    pub sight_radius: f32, // How far the building sees, measured from its edge
+    pub attack_range: f32,
+    pub damage: i32,
+    pub damage_type: DamageType,
+    pub cooldown: i32,
+    pub homing: bool, // Arrows follow the target instead of flying to where it was
+}
+
+impl BuildingDefinition {
+    pub fn can_produce(&self, entity_type: &EntityType) -> bool {
+        self.produces.contains(entity_type)
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct BuildingRegistry {
+    definitions: Vec<Rc<BuildingDefinition>>,
+}
+
+impl Default for BuildingRegistry {
+    // The definitions that were shipped with the game
+    fn default() -> Self {
+        BuildingRegistry::parse(include_str!("../assets/building_types.ron")).unwrap()
+    }
+}
+
+impl BuildingRegistry {
+    pub fn parse(text: &str) -> io::Result<BuildingRegistry> {
+        let building_registry: BuildingRegistry =
+            ron::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        // Game::new places a town hall for every team
+        if building_registry.get(BuildingType::TownHall).is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the town hall building type is missing",
+            ));
+        }
+        Ok(building_registry)
+    }
+
+    pub fn load(path: &str) -> io::Result<BuildingRegistry> {
+        BuildingRegistry::parse(&fs::read_to_string(path)?)
+    }
+
+    pub fn load_or_default(path: &str) -> BuildingRegistry {
+        match BuildingRegistry::load(path) {
+            Ok(building_registry) => building_registry,
+            Err(e) => {
+                println!(
+                    "Could not load {}, using default building definitions: {}",
+                    path, e
+                );
+                BuildingRegistry::default()
+            }
+        }
+    }
+
+    pub fn get(&self, building_type: BuildingType) -> Option<&Rc<BuildingDefinition>> {
+        self.definitions
+            .iter()
+            .find(|definition| definition.building_type == building_type)
+    }
+
+    pub fn get_definitions(&self) -> &[Rc<BuildingDefinition>] {
+        &self.definitions
+    }
+}
@@ -1,5 +1,5 @@
-use crate::constants::{GROUND_HEIGHT, GROUND_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH, TILE_HW_RATIO};
-use crate::vec;
+use rts2::constants::{GROUND_HEIGHT, GROUND_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH, TILE_HW_RATIO};
+use rts2::vec;
 
 #[derive(Debug, Clone)]
 pub struct Camera {
@@ -15,10 +15,6 @@ impl Camera {
         }
     }
 
-    pub fn set_zoom(&mut self, zoom: f32) {
-        self.zoom = zoom;
-    }
-
     pub fn set_position(&mut self, position: &vec::Vec2f) {
         self.position = position.clone();
     }
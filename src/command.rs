@@ -0,0 +1,86 @@
+use crate::building_registry::BuildingType;
+use crate::entity::AttackTarget;
+use crate::entity_registry::EntityType;
+use crate::ground::GroundType;
+use crate::handle::Handle;
+use crate::vec::{Vec2f, Vec2i};
+use serde::{Deserialize, Serialize};
+
+// A player action. Everything that changes the simulation from the outside (input, AI, network,
+// replays) goes through Game::queue_command. Queued commands are applied at the start of the
+// next Game::update so that the frame timing does not affect the simulation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum Command {
+    Move {
+        entity_ids: Vec<Handle>,
+        position: Vec2f,
+    },
+    // Move, but fight the enemies that are met on the way
+    AttackMove {
+        entity_ids: Vec<Handle>,
+        position: Vec2f,
+    },
+    // Follow and attack one enemy unit or building until it is gone
+    Attack {
+        entity_ids: Vec<Handle>,
+        target: AttackTarget,
+    },
+    // Only workers take part in gathering
+    Gather {
+        entity_ids: Vec<Handle>,
+        position: Vec2i,
+    },
+    // Help constructing an already placed building, or repair it when it is damaged
+    Build {
+        entity_ids: Vec<Handle>,
+        building_id: Handle,
+    },
+    // Place a new building and start constructing it
+    ConstructBuilding {
+        entity_ids: Vec<Handle>,
+        top_left: Vec2i,
+        building_type: BuildingType,
+    },
+    // Remove a building that is still under construction, refunding part of its cost. Only the
+    // team owning the building can remove it.
+    CancelConstruction {
+        building_id: Handle,
+        team: u8,
+    },
+    // Remove a finished building. Only the units in its spawn queue are refunded.
+    Demolish {
+        building_id: Handle,
+        team: u8,
+    },
+    Spawn {
+        building_id: Handle,
+        entity_type: EntityType,
+    },
+    // Remove an item from the spawn queue and refund it. None cancels the last item.
+    CancelSpawn {
+        building_id: Handle,
+        index: Option<usize>,
+    },
+    // Where the entities spawned by the building go to
+    Rally {
+        building_id: Handle,
+        position: Vec2f,
+    },
+    Hold {
+        entity_ids: Vec<Handle>,
+    },
+    Idle {
+        entity_ids: Vec<Handle>,
+    },
+    // Debug tool for painting walls
+    SetGround {
+        position: Vec2i,
+        ground_type: GroundType,
+    },
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    pub tick: u32,
+    pub command: Command,
+}
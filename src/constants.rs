@@ -5,4 +5,18 @@ pub const GROUND_WIDTH: i32 = 80;
 pub const GROUND_HEIGHT: i32 = 60;
 pub const ENTITY_AMOUNT: usize = 80;
 
+pub const MAX_SPAWN_QUEUE_LENGTH: usize = 5;
+pub const CANCEL_CONSTRUCTION_REFUND_PERCENT: i32 = 75;
+pub const CONSTRUCTION_START_HEALTH_PERCENT: i32 = 10; // Health of a newly placed building
+pub const MAX_SUPPLY: i32 = 200; // Supply cap can not go above this however many farms there are
+
+pub const RESOURCE_DEPOSIT_AMOUNT: i32 = 300; // What one resource tile holds
+pub const GATHER_AMOUNT: i32 = 20; // What a worker carries per trip
+
+pub const REPAIR_AMOUNT: i32 = 10; // Health restored by one repair step of a worker
+pub const REPAIR_COST_PERCENT: i32 = 50; // Share of the building cost that a full repair costs
+
+pub const SPLASH_EDGE_DAMAGE_PERCENT: i32 = 25; // Damage at the edge of a splash, full in the center
+pub const PROJECTILE_RADIUS: f32 = 0.1; // Added to the radius of a unit when checking for hits
+
 pub const TILE_HW_RATIO: f32 = 0.75;
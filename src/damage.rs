@@ -0,0 +1,92 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DamageType {
+    Melee,
+    Pierce,
+    Siege,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ArmorType {
+    Light,     // E.g. workers and ranged units
+    Heavy,     // E.g. melee units
+    Fortified, // Buildings
+}
+
+// Percent of the damage that is dealt, by damage type (rows) against armor type (columns)
+const DAMAGE_MODIFIERS: [[i32; 3]; 3] = [
+    // Light, Heavy, Fortified
+    [100, 100, 50], // Melee
+    [125, 75, 25],  // Pierce
+    [50, 50, 300],  // Siege
+];
+
+pub fn get_damage_modifier_percent(damage_type: DamageType, armor_type: ArmorType) -> i32 {
+    DAMAGE_MODIFIERS[damage_type as usize][armor_type as usize]
+}
+
+// Damage after the modifier and then the armor is applied, a hit always does at least 1 damage
+pub fn calculate_damage(
+    damage: i32,
+    damage_type: DamageType,
+    armor: i32,
+    armor_type: ArmorType,
+) -> i32 {
+    let modified_damage = damage * get_damage_modifier_percent(damage_type, armor_type) / 100;
+    (modified_damage - armor).max(1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn modifiers_follow_the_table() {
+        assert_eq!(
+            get_damage_modifier_percent(DamageType::Melee, ArmorType::Light),
+            100
+        );
+        assert_eq!(
+            get_damage_modifier_percent(DamageType::Melee, ArmorType::Fortified),
+            50
+        );
+        assert_eq!(
+            get_damage_modifier_percent(DamageType::Pierce, ArmorType::Light),
+            125
+        );
+        assert_eq!(
+            get_damage_modifier_percent(DamageType::Pierce, ArmorType::Heavy),
+            75
+        );
+        assert_eq!(
+            get_damage_modifier_percent(DamageType::Siege, ArmorType::Fortified),
+            300
+        );
+
+        assert_eq!(
+            calculate_damage(8, DamageType::Pierce, 0, ArmorType::Light),
+            10
+        );
+        assert_eq!(
+            calculate_damage(10, DamageType::Siege, 5, ArmorType::Fortified),
+            25
+        );
+    }
+
+    #[test]
+    fn hits_always_do_at_least_one_damage() {
+        assert_eq!(
+            calculate_damage(4, DamageType::Pierce, 0, ArmorType::Fortified),
+            1
+        );
+        assert_eq!(
+            calculate_damage(5, DamageType::Melee, 10, ArmorType::Heavy),
+            1
+        );
+        assert_eq!(
+            calculate_damage(0, DamageType::Siege, 0, ArmorType::Light),
+            1
+        );
+    }
+}
@@ -1,6 +1,6 @@
 use crate::camera::Camera;
-use crate::vec::Vec2f;
 use raqote::{DrawOptions, DrawTarget, PathBuilder, SolidSource, Source};
+use rts2::vec::Vec2f;
 
 pub fn draw_health_bar(
     dt: &mut DrawTarget,
@@ -1,23 +1,23 @@
 use crate::building::Building;
+use crate::building_container::BuildingContainer;
+use crate::entity_registry::{EntityDefinition, EntityType, ProjectileKind};
 use crate::event_handler::{Event, EventHandler};
 use crate::ground::Ground;
+use crate::handle::Handle;
 use crate::health::Health;
 use crate::path_finder::{distance_to_big_block, Path, PathFinder, PathGoal};
-use crate::resources::Resources;
+use crate::projectile::ProjectilePayload;
+use crate::resources::{ResourceType, Resources};
+use crate::save::PathTable;
 use crate::spacial_partition::SpaciallyPartitionable;
 use crate::vec::{Vec2f, Vec2i};
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::collections::HashSet;
 use std::fmt::{Debug, Formatter};
+use std::io;
 use std::rc::Rc;
 
-#[derive(Clone, Debug)]
-pub enum EntityType {
-    Melee,
-    Ranged,
-    Worker,
-}
-
 #[derive(Clone)]
 pub struct Goal {
     group_size: f32,
@@ -40,33 +40,61 @@ impl Debug for BuildGoal {
 pub struct GatherGoal {
     resource_position: Vec2i,
     // building: Option<GatherGoalBuilding>,
-    resource_type: u8,
+    resource_type: ResourceType,
     going_towards_resource: bool,
+    carrying: i32, // Taken from the deposit, added to the team at the drop-off building
     counter: i32,
     path: Option<Rc<RefCell<Path>>>,
+    drop_off_building: Option<Handle>, // Where the path leads to when going back
 }
 
+// Buildings are referred to by handle so that a destroyed building is not kept alive by its
+// builders
 #[derive(Clone)]
 pub struct BuildGoal {
-    building: Rc<RefCell<Building>>, // TODO: Should we store reference to the building here or rather something else?
+    building: Handle,
     path: Option<Rc<RefCell<Path>>>,
     counter: i32,
 }
 
+// What an attack command was given on
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AttackTarget {
+    Entity(Handle),
+    Building(Handle),
+}
+
+// Attack a specific target until it is gone. The path is found again when the target has moved
+// away from its goal.
+#[derive(Clone)]
+pub struct TargetGoal {
+    target: AttackTarget,
+    path: Option<Rc<RefCell<Path>>>,
+    counter: i32, // Ticks since the last path request
+}
+
+impl Debug for TargetGoal {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "TargetGoal")
+    }
+}
+
 impl Debug for GatherGoal {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         write!(f, "GatherGoal")
     }
 }
 
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Default)]
 pub enum EntityAction {
     Move(Goal),
     Attack(Goal),
+    #[default]
     Idle,
     Gather(GatherGoal),
     Hold,
     Build(BuildGoal),
+    AttackTarget(TargetGoal),
 }
 
 impl EntityAction {
@@ -80,22 +108,64 @@ impl EntityAction {
                     return None;
                 }
             }
+            EntityAction::AttackTarget(ref goal) => {
+                if let Some(path) = &goal.path {
+                    path
+                } else {
+                    return None;
+                }
+            }
             _ => return None,
         };
         Some(path_ref.borrow().goal.clone())
     }
 }
 
+// EntityAction as it is written to a save file. Paths are referred to by their index in the
+// saved path list and buildings by their id.
+#[derive(Serialize, Deserialize)]
+pub enum SavedEntityAction {
+    Move {
+        group_size: f32,
+        path: usize,
+    },
+    Attack {
+        group_size: f32,
+        path: usize,
+    },
+    Idle,
+    Gather {
+        resource_position: Vec2i,
+        resource_type: ResourceType,
+        going_towards_resource: bool,
+        carrying: i32,
+        counter: i32,
+        path: Option<usize>,
+        drop_off_building: Option<Handle>,
+    },
+    Hold,
+    Build {
+        building_id: Handle,
+        path: Option<usize>,
+        counter: i32,
+    },
+    AttackTarget {
+        target: AttackTarget,
+        path: Option<usize>,
+        counter: i32,
+    },
+}
+
+#[derive(Serialize, Deserialize)]
 pub struct Entity {
     position: Vec2f,
     next_position: Vec2f,
+    #[serde(skip)] // Saved separately, see SavedEntityAction
     action: EntityAction,
-    speed: f32,
-    id: usize,
-    radius: f32,
+    id: Handle,
     team: u8,
     projectile_cooldown: i32,
-    entity_type: EntityType,
+    definition: Rc<EntityDefinition>,
     pub health: Health,
 }
 
@@ -148,36 +218,34 @@ impl SpaciallyPartitionable<EntityFilter> for Entity {
                 return false;
             }
         }
-        return true;
+        true
     }
 }
 
 impl Entity {
-    pub fn new_params(position: Vec2f, team: u8, entity_type: EntityType) -> Entity {
-        let mut entity = Entity::new(position);
-
-        let radius = match entity_type {
-            EntityType::Melee => 0.35,
-            EntityType::Ranged => 0.4,
-            EntityType::Worker => 0.25,
-        };
-
-        entity.team = team;
-        entity.radius = radius;
-        entity.entity_type = entity_type;
-
-        entity
+    pub fn new(position: Vec2f, team: u8, definition: Rc<EntityDefinition>) -> Entity {
+        Entity {
+            position: position.clone(),
+            next_position: position.clone(),
+            action: EntityAction::Idle,
+            id: Handle::default(), // Set by EntityContainer::spawn_entity
+            team,
+            projectile_cooldown: 0,
+            health: Health::new(definition.health),
+            definition,
+        }
     }
 
     pub fn is_worker(&self) -> bool {
-        match self.entity_type {
-            EntityType::Worker => true,
-            _ => false,
-        }
+        self.definition.is_worker
     }
 
     pub fn get_entity_type(&self) -> EntityType {
-        self.entity_type.clone()
+        self.definition.entity_type.clone()
+    }
+
+    pub fn get_definition(&self) -> &EntityDefinition {
+        &self.definition
     }
 
     pub fn refresh_path(&mut self, path_finder: &mut PathFinder, ground: &Ground) {
@@ -194,55 +262,28 @@ impl Entity {
         }
     }
 
-    pub fn new(position: Vec2f) -> Entity {
-        let random_id = rand::random::<usize>();
-        // Radius should be random between 0.25 and 0.5
-        let random_radius = rand::random::<f32>() / 4.0 + 0.25;
-        // let random_radius = 0.5;
-        let random_team = rand::random::<u8>() % 2;
-
-        let random_entity_type = if rand::random::<f32>() < 0.5 {
-            EntityType::Melee
-        } else {
-            EntityType::Ranged
-        };
-
-        Entity {
-            position: position.clone(),
-            next_position: position.clone(),
-            action: EntityAction::Idle,
-            speed: 0.05,
-            id: random_id,
-            radius: random_radius,
-            team: random_team,
-            projectile_cooldown: 0,
-            health: Health::new(100),
-            entity_type: random_entity_type,
-        }
-    }
-
     pub fn get_team(&self) -> u8 {
         self.team
     }
 
     pub fn get_radius(&self) -> f32 {
-        self.radius
+        self.definition.radius
     }
 
-    pub fn get_id(&self) -> usize {
+    pub fn get_id(&self) -> Handle {
         self.id
     }
 
+    pub fn set_id(&mut self, id: Handle) {
+        self.id = id;
+    }
+
     fn set_action(&mut self, action: EntityAction) {
         // println!("set_action {:?}", action);
         self.action = action;
     }
 
-    pub fn set_action_build(
-        &mut self,
-        building: Rc<RefCell<Building>>,
-        path: Option<Rc<RefCell<Path>>>,
-    ) {
+    pub fn set_action_build(&mut self, building: Handle, path: Option<Rc<RefCell<Path>>>) {
         self.set_action(EntityAction::Build(BuildGoal {
             building,
             path,
@@ -258,7 +299,12 @@ impl Entity {
         self.set_action(EntityAction::Idle);
     }
 
-    pub fn set_action_move(&mut self, path: Rc<RefCell<Path>>, goal: &Vec2f, goal_group_size: f32) {
+    pub fn set_action_move(
+        &mut self,
+        path: Rc<RefCell<Path>>,
+        _goal: &Vec2f,
+        goal_group_size: f32,
+    ) {
         self.set_action(EntityAction::Move(Goal {
             path,
             // position: goal.clone(),
@@ -269,7 +315,7 @@ impl Entity {
     pub fn set_action_attack(
         &mut self,
         path: Rc<RefCell<Path>>,
-        goal: &Vec2f,
+        _goal: &Vec2f,
         goal_group_size: f32,
     ) {
         self.set_action(EntityAction::Attack(Goal {
@@ -279,21 +325,133 @@ impl Entity {
         }));
     }
 
+    pub fn set_action_attack_target(
+        &mut self,
+        target: AttackTarget,
+        path: Option<Rc<RefCell<Path>>>,
+    ) {
+        self.set_action(EntityAction::AttackTarget(TargetGoal {
+            target,
+            path,
+            counter: 0,
+        }));
+    }
+
     pub fn set_action_gather(
         &mut self,
         resource_position: Vec2i,
-        resource_type: u8,
+        resource_type: ResourceType,
         path: Option<Rc<RefCell<Path>>>,
     ) {
         self.set_action(EntityAction::Gather(GatherGoal {
             resource_position,
             resource_type,
             going_towards_resource: true,
+            carrying: 0,
             counter: 0,
             path,
+            drop_off_building: None,
         }));
     }
 
+    pub fn get_saved_action(&self, path_table: &mut PathTable) -> SavedEntityAction {
+        match &self.action {
+            EntityAction::Move(goal) => SavedEntityAction::Move {
+                group_size: goal.group_size,
+                path: path_table.index_of(&goal.path),
+            },
+            EntityAction::Attack(goal) => SavedEntityAction::Attack {
+                group_size: goal.group_size,
+                path: path_table.index_of(&goal.path),
+            },
+            EntityAction::Idle => SavedEntityAction::Idle,
+            EntityAction::Gather(goal) => SavedEntityAction::Gather {
+                resource_position: goal.resource_position.clone(),
+                resource_type: goal.resource_type,
+                going_towards_resource: goal.going_towards_resource,
+                carrying: goal.carrying,
+                counter: goal.counter,
+                path: goal.path.as_ref().map(|path| path_table.index_of(path)),
+                drop_off_building: goal.drop_off_building,
+            },
+            EntityAction::Hold => SavedEntityAction::Hold,
+            EntityAction::Build(goal) => SavedEntityAction::Build {
+                building_id: goal.building,
+                path: goal.path.as_ref().map(|path| path_table.index_of(path)),
+                counter: goal.counter,
+            },
+            EntityAction::AttackTarget(goal) => SavedEntityAction::AttackTarget {
+                target: goal.target,
+                path: goal.path.as_ref().map(|path| path_table.index_of(path)),
+                counter: goal.counter,
+            },
+        }
+    }
+
+    // Fails when the save refers to a path that is not in its path table
+    pub fn restore_action(
+        &mut self,
+        saved_action: SavedEntityAction,
+        paths: &[Rc<RefCell<Path>>],
+    ) -> io::Result<()> {
+        let get_path = |path: usize| {
+            paths.get(path).cloned().ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Path {} not found in the save", path),
+                )
+            })
+        };
+        self.action = match saved_action {
+            SavedEntityAction::Move { group_size, path } => EntityAction::Move(Goal {
+                group_size,
+                path: get_path(path)?,
+            }),
+            SavedEntityAction::Attack { group_size, path } => EntityAction::Attack(Goal {
+                group_size,
+                path: get_path(path)?,
+            }),
+            SavedEntityAction::Idle => EntityAction::Idle,
+            SavedEntityAction::Gather {
+                resource_position,
+                resource_type,
+                going_towards_resource,
+                carrying,
+                counter,
+                path,
+                drop_off_building,
+            } => EntityAction::Gather(GatherGoal {
+                resource_position,
+                resource_type,
+                going_towards_resource,
+                carrying,
+                counter,
+                path: path.map(get_path).transpose()?,
+                drop_off_building,
+            }),
+            SavedEntityAction::Hold => EntityAction::Hold,
+            SavedEntityAction::Build {
+                building_id,
+                path,
+                counter,
+            } => EntityAction::Build(BuildGoal {
+                building: building_id,
+                path: path.map(get_path).transpose()?,
+                counter,
+            }),
+            SavedEntityAction::AttackTarget {
+                target,
+                path,
+                counter,
+            } => EntityAction::AttackTarget(TargetGoal {
+                target,
+                path: path.map(get_path).transpose()?,
+                counter,
+            }),
+        };
+        Ok(())
+    }
+
     pub fn get_goal(&self) -> Option<Vec2f> {
         let path_goal = self.action.get_path_goal();
         // let path_ref = match self.action {
@@ -339,12 +497,73 @@ impl Entity {
                 goal.path = path;
                 // goal.building = building;
             }
+            EntityAction::AttackTarget(ref mut goal) => {
+                goal.path = path;
+            }
             _ => {
                 println!("update_path called on non gather action (NOT YET IMPLEMENTED)");
             }
         }
     }
 
+    // Called when the path to the drop-off building of a gather action is found
+    pub fn set_drop_off_building(&mut self, building: Option<Handle>) {
+        if let EntityAction::Gather(ref mut goal) = self.action {
+            goal.drop_off_building = building;
+        }
+    }
+
+    pub fn get_build_target(&self) -> Option<Handle> {
+        match &self.action {
+            EntityAction::Build(goal) => Some(goal.building),
+            _ => None,
+        }
+    }
+
+    pub fn get_attack_target(&self) -> Option<AttackTarget> {
+        match &self.action {
+            EntityAction::AttackTarget(goal) => Some(goal.target),
+            _ => None,
+        }
+    }
+
+    // Units without a projectile, e.g. workers, do not fight
+    pub fn can_attack(&self) -> bool {
+        self.definition.projectile_kind != ProjectileKind::None
+    }
+
+    pub fn get_gather_resource_type(&self) -> Option<ResourceType> {
+        match &self.action {
+            EntityAction::Gather(goal) => Some(goal.resource_type),
+            _ => None,
+        }
+    }
+
+    // True when on the way to (or mining) the deposit at the position
+    pub fn is_going_to_gather_at(&self, position: &Vec2i) -> bool {
+        match &self.action {
+            EntityAction::Gather(goal) => {
+                goal.going_towards_resource && goal.resource_position == *position
+            }
+            _ => false,
+        }
+    }
+
+    // Called when the deposit is depleted and another one of the same type was found
+    pub fn set_gather_resource_position(&mut self, position: Vec2i) {
+        if let EntityAction::Gather(ref mut goal) = self.action {
+            goal.resource_position = position;
+        }
+    }
+
+    // Called when the resources were taken from the deposit, the entity heads back next
+    pub fn set_gather_carrying(&mut self, amount: i32) {
+        if let EntityAction::Gather(ref mut goal) = self.action {
+            goal.carrying = amount;
+            goal.going_towards_resource = false;
+        }
+    }
+
     pub fn get_position(&self) -> Vec2f {
         self.position.clone()
     }
@@ -359,13 +578,13 @@ impl Entity {
         let delta = self.position.clone() - other_position;
         if delta.length() == 0.0 {
             println!("Delta length is 0.0");
-            let random_value =
-                (self.id & 18446744073709551615) as f32 / 18446744073709551615.0 / 10.0;
+            // Differs between entities so that entities on top of each other get separated
+            let random_value = (self.id.get_index() * 7919 % 1000) as f32 / 1000.0 / 10.0;
             self.next_position += Vec2f::new(random_value, random_value * 0.5);
             return;
         }
 
-        let radius_sum = self.radius + other_radius;
+        let radius_sum = self.definition.radius + other_radius;
 
         let delta_length = delta.length();
         if delta_length < radius_sum {
@@ -385,6 +604,12 @@ impl Entity {
         }
     }
 
+    // Moves the entity right away, e.g. out of a newly placed building
+    pub fn set_position(&mut self, position: Vec2f) {
+        self.position = position.clone();
+        self.next_position = position;
+    }
+
     pub fn flip_position(&mut self) {
         self.position = self.next_position.clone();
     }
@@ -403,7 +628,7 @@ impl Entity {
             }
         }
 
-        let radius = self.radius;
+        let radius = self.definition.radius;
 
         // Partially inside a block directly side by side
         if ground.is_blocked(&(self.next_position.clone() + Vec2f::new(radius, 0.0))) {
@@ -524,7 +749,7 @@ impl Entity {
     // Helper for fn update
     fn move_towards_goal(&mut self, goal: &Vec2f, step_delta: f32) {
         let delta = goal.clone() - self.position.clone();
-        self.next_position += delta.normalized() * self.speed * step_delta;
+        self.next_position += delta.normalized() * self.definition.speed * step_delta;
     }
 
     fn move_towards_path(
@@ -536,7 +761,7 @@ impl Entity {
         let path = path.borrow();
 
         match &path.goal {
-            PathGoal::Rect { pos, size } => {
+            PathGoal::Rect { pos: _, size: _ } => {
                 // TODO: Should perhaps move towards the big block here?
                 // Or is it enough to move towards the path finding arrows instead?
             }
@@ -550,10 +775,10 @@ impl Entity {
 
         let mut directions: Vec<Vec2f> = Vec::new();
         for i in [
-            (self.position.clone() + Vec2f::new(0.0, self.radius)).as_vec2i(),
-            (self.position.clone() + Vec2f::new(0.0, -self.radius)).as_vec2i(),
-            (self.position.clone() + Vec2f::new(self.radius, 0.0)).as_vec2i(),
-            (self.position.clone() + Vec2f::new(-self.radius, 0.0)).as_vec2i(),
+            (self.position.clone() + Vec2f::new(0.0, self.definition.radius)).as_vec2i(),
+            (self.position.clone() + Vec2f::new(0.0, -self.definition.radius)).as_vec2i(),
+            (self.position.clone() + Vec2f::new(self.definition.radius, 0.0)).as_vec2i(),
+            (self.position.clone() + Vec2f::new(-self.definition.radius, 0.0)).as_vec2i(),
         ] {
             if let Some(direction) = path.get_direction(&i) {
                 directions.push(direction);
@@ -579,29 +804,83 @@ impl Entity {
         self.move_towards_goal(&asdf_goal, step_delta);
     }
 
+    // Ranged attacks need a free line to the target, boulders too since the target has to be
+    // seen. Melee attacks are too close to be blocked.
+    fn is_shot_blocked(&self, ground: &Ground, target_position: &Vec2f) -> bool {
+        match self.definition.projectile_kind {
+            ProjectileKind::Arrow | ProjectileKind::Boulder | ProjectileKind::Bolt => {
+                !ground.has_line_of_sight(&self.position, target_position)
+            }
+            ProjectileKind::Melee | ProjectileKind::None => false,
+        }
+    }
+
+    // A wall is in the way of the shot, walk to where the target can be shot from. Towards the
+    // target when there is no such place close by.
+    fn move_to_line_of_sight(
+        &mut self,
+        ground: &Ground,
+        target_position: &Vec2f,
+        range: f32,
+        step_delta: f32,
+    ) {
+        let max_distance = (range * 2.0).ceil() as i32;
+        match ground.find_line_of_sight_step(&self.position, target_position, range, max_distance) {
+            Some(position) => self.move_towards_goal(&position, step_delta),
+            None => self.move_towards_goal(target_position, step_delta),
+        }
+    }
+
     fn potentially_launch_projectile(
         &mut self,
         event_handler: &mut EventHandler,
         goal_position: &Vec2f,
+        target: AttackTarget,
     ) {
         if self.projectile_cooldown == 0 {
-            match self.entity_type {
-                EntityType::Ranged => {
+            let payload = ProjectilePayload {
+                damage: self.definition.damage,
+                damage_type: self.definition.damage_type,
+                splash_radius: self.definition.splash_radius,
+                friendly_fire: self.definition.friendly_fire,
+            };
+            match self.definition.projectile_kind {
+                ProjectileKind::Arrow | ProjectileKind::Boulder => {
                     event_handler.add_event(Event::AddRangedProjectile {
                         start: self.position.clone(),
                         end: goal_position.clone(),
+                        kind: self.definition.projectile_kind,
+                        payload,
                         team: self.team,
+                        homing_target: if self.definition.homing {
+                            Some(target)
+                        } else {
+                            None
+                        },
                     });
                 }
-                EntityType::Melee => {
+                ProjectileKind::Bolt => {
+                    // Does not stop at the target
+                    let direction = (goal_position.clone() - self.position.clone()).normalized();
+                    event_handler.add_event(Event::AddRangedProjectile {
+                        start: self.position.clone(),
+                        end: self.position.clone() + direction * self.definition.attack_range,
+                        kind: self.definition.projectile_kind,
+                        payload,
+                        team: self.team,
+                        homing_target: None, // Flies straight on past the target
+                    });
+                }
+                ProjectileKind::Melee => {
                     event_handler.add_event(Event::AddMeleeProjectile {
                         end: goal_position.clone(),
+                        payload,
                         team: self.team,
                     });
                 }
-                EntityType::Worker => {}
+                ProjectileKind::None => {}
             }
-            self.projectile_cooldown = 100;
+            self.projectile_cooldown = self.definition.cooldown;
         } else {
             self.projectile_cooldown -= 1;
         }
@@ -611,6 +890,7 @@ impl Entity {
     fn interact_with_closest_enemy_building(
         &mut self,
         closest_enemy_building: &Rc<RefCell<Building>>,
+        ground: &Ground,
         step_n: i32,
         step_delta: f32,
         can_move: bool,
@@ -630,35 +910,38 @@ impl Entity {
         // let enemy_position = closest_enemy.borrow().position.clone();
         // let delta = enemy_position.clone() - self.position.clone();
         // let delta_length = delta.length();
-        // let combined_length = self.radius + closest_enemy.borrow().radius;
+        // let combined_length = self.definition.radius + closest_enemy.borrow().definition.radius;
 
-        let min_range = match self.entity_type {
-            EntityType::Ranged => 5.0,
-            EntityType::Melee => self.radius + 0.1,
-            EntityType::Worker => {
+        let min_range = match self.definition.projectile_kind {
+            ProjectileKind::Arrow | ProjectileKind::Boulder | ProjectileKind::Bolt => {
+                self.definition.attack_range
+            }
+            ProjectileKind::Melee => self.definition.radius + self.definition.attack_range,
+            ProjectileKind::None => {
                 // Workers do not interact with enemies
                 // TODO: Should it run away?
                 return;
             }
         };
 
-        if distance_to_building > min_range {
+        // The closest part of the building is aimed at
+        let aim_position = closest_enemy_building.get_closest_tile_center(&self.position);
+
+        if self.is_shot_blocked(ground, &aim_position) {
+            if can_move {
+                self.move_to_line_of_sight(ground, &aim_position, min_range, step_delta);
+            }
+        } else if distance_to_building > min_range {
             if can_move {
                 self.move_towards_goal(&closest_enemy_building.get_center_position(), step_delta);
             }
         } else {
             if step_n == 0 {
-                let building_center_position = closest_enemy_building.get_center_position();
-                let distance_to_building_center =
-                    (self.position.clone() - building_center_position.clone()).length();
-
-                let projectile_goal_pos = if distance_to_building_center > min_range {
-                    // TODO: Here we should aim towards the closest corner of the building (or closest side?)
-                    building_center_position
-                } else {
-                    building_center_position
-                };
-                self.potentially_launch_projectile(event_handler, &projectile_goal_pos);
+                self.potentially_launch_projectile(
+                    event_handler,
+                    &aim_position,
+                    AttackTarget::Building(closest_enemy_building.get_id()),
+                );
             }
         }
     }
@@ -667,6 +950,7 @@ impl Entity {
     fn interact_with_closest_enemy(
         &mut self,
         closest_enemy: &Rc<RefCell<Entity>>,
+        ground: &Ground,
         step_n: i32,
         step_delta: f32,
         can_move: bool,
@@ -675,50 +959,180 @@ impl Entity {
         let enemy_position = closest_enemy.borrow().position.clone();
         let delta = enemy_position.clone() - self.position.clone();
         let delta_length = delta.length();
-        let combined_length = self.radius + closest_enemy.borrow().radius;
+        let combined_length = self.definition.radius + closest_enemy.borrow().definition.radius;
 
-        let min_range = match self.entity_type {
-            EntityType::Ranged => 5.0,
-            EntityType::Melee => combined_length + 0.1,
-            EntityType::Worker => {
+        let min_range = match self.definition.projectile_kind {
+            ProjectileKind::Arrow | ProjectileKind::Boulder | ProjectileKind::Bolt => {
+                self.definition.attack_range
+            }
+            ProjectileKind::Melee => combined_length + self.definition.attack_range,
+            ProjectileKind::None => {
                 // Workers do not interact with enemies
                 // TODO: Should it run away?
                 return;
             }
         };
 
-        if delta_length > min_range {
+        if self.is_shot_blocked(ground, &enemy_position) {
+            if can_move {
+                self.move_to_line_of_sight(ground, &enemy_position, min_range, step_delta);
+            }
+        } else if delta_length > min_range {
             if can_move {
                 self.move_towards_goal(&enemy_position, step_delta);
             }
         } else {
             if step_n == 0 {
-                self.potentially_launch_projectile(event_handler, &closest_enemy.borrow().position);
+                let closest_enemy = closest_enemy.borrow();
+                self.potentially_launch_projectile(
+                    event_handler,
+                    &closest_enemy.position,
+                    AttackTarget::Entity(closest_enemy.id),
+                );
+            }
+        }
+    }
+
+    // Follows the target and attacks it when in range. Returns true when the target is gone.
+    #[allow(clippy::too_many_arguments)]
+    pub fn handle_attack_target(
+        &mut self,
+        goal: &mut TargetGoal,
+        target_entity: Option<Rc<RefCell<Entity>>>,
+        building_container: &BuildingContainer,
+        ground: &Ground,
+        step_n: i32,
+        step_delta: f32,
+        event_handler: &mut EventHandler,
+    ) -> bool {
+        let range = match self.definition.projectile_kind {
+            ProjectileKind::Arrow | ProjectileKind::Boulder | ProjectileKind::Bolt => {
+                self.definition.attack_range
+            }
+            ProjectileKind::Melee => self.definition.radius + self.definition.attack_range,
+            ProjectileKind::None => return true,
+        };
+
+        // How far the target is outside of the attack range, where to aim and the path goal
+        let (distance_outside_range, aim_position, target_top_left, target_size) = match goal.target
+        {
+            AttackTarget::Entity(_) => {
+                let target_entity = match &target_entity {
+                    Some(target_entity) => target_entity.borrow(),
+                    None => return true, // Dead
+                };
+                let target_position = target_entity.get_position();
+                let mut distance = (target_position.clone() - self.position.clone()).length();
+                if self.definition.projectile_kind == ProjectileKind::Melee {
+                    distance -= target_entity.get_radius();
+                }
+                (
+                    distance - range,
+                    target_position.clone(),
+                    target_position.as_vec2i(),
+                    Vec2i::new(1, 1),
+                )
+            }
+            AttackTarget::Building(building_id) => {
+                let building_ref = match building_container.get_building_by_id(building_id) {
+                    Some(building_ref) => building_ref,
+                    None => return true, // Destroyed
+                };
+                let building = building_ref.borrow();
+                let size = Vec2i::new(building.get_width(), building.get_height());
+                let distance =
+                    distance_to_big_block(&self.position, &building.get_position(), &size);
+                (
+                    distance - range,
+                    building.get_closest_tile_center(&self.position),
+                    building.get_position(),
+                    size,
+                )
+            }
+        };
+
+        let is_shot_blocked = self.is_shot_blocked(ground, &aim_position);
+        if distance_outside_range <= 0.0 && !is_shot_blocked {
+            if step_n == 0 {
+                self.potentially_launch_projectile(event_handler, &aim_position, goal.target);
             }
+            return false;
         }
+
+        // Off the path, e.g. when the target can not be reached, the target is walked towards
+        // directly until a new path is found
+        let is_on_path = goal.path.as_ref().is_some_and(|path| {
+            path.borrow()
+                .get_direction(&self.position.as_vec2i())
+                .is_some()
+        });
+
+        if step_n == 0 {
+            goal.counter += 1;
+            let is_path_outdated = match &goal.path {
+                Some(path) => match &path.borrow().goal {
+                    PathGoal::Rect { pos, .. } => *pos != target_top_left || !is_on_path,
+                    PathGoal::Point { .. } => true,
+                },
+                None => true,
+            };
+            // Not more often than every 20 ticks, e.g. when there is no path to the target
+            if is_path_outdated && goal.counter > 20 {
+                goal.counter = 0;
+                event_handler.add_event(Event::RequestAttackPath {
+                    entity_id: self.id,
+                    path_goal: PathGoal::Rect {
+                        pos: target_top_left,
+                        size: target_size,
+                    },
+                });
+            }
+        }
+
+        match &goal.path {
+            // Close to the target the path is of no use anymore, unless it leads around a wall
+            Some(path) if is_on_path && (distance_outside_range > 1.0 || is_shot_blocked) => {
+                self.move_towards_path(path.clone(), step_delta, event_handler)
+            }
+            _ => self.move_towards_goal(&aim_position, step_delta),
+        }
+        false
     }
 
+    // Constructs the building, or repairs it when it is already constructed. Returns true when
+    // there is nothing left to do.
     pub fn handle_build(
         &mut self,
         goal: &mut BuildGoal,
+        building_container: &BuildingContainer,
         step_n: i32,
         step_delta: f32,
         event_handler: &mut EventHandler,
-    ) {
+    ) -> bool {
+        let building = match building_container.get_building_by_id(goal.building) {
+            Some(building) => building,
+            None => return true, // Destroyed
+        };
+        let is_constructed = building.borrow().is_constructed();
+        if is_constructed && building.borrow().health.is_full() {
+            return true;
+        }
+
         if let Some(path_ref) = &goal.path {
             let path = path_ref.borrow();
-            if path.distance_to_goal(&self.position) < self.radius + 0.1 {
+            if path.distance_to_goal(&self.position) < self.definition.radius + 0.1 {
                 if step_n == 0 {
                     goal.counter += 1;
                     if goal.counter > 30 {
                         // Building build counter is incremented 2x per sec
                         goal.counter = 0;
-                        goal.building
-                            .borrow_mut()
-                            .construction_progress
-                            .increment(1);
-                        if goal.building.borrow().is_constructed() {
-                            self.action = EntityAction::Idle;
+                        if is_constructed {
+                            event_handler.add_event(Event::RepairBuilding {
+                                entity_id: self.id,
+                                building_id: goal.building,
+                            });
+                        } else {
+                            building.borrow_mut().add_construction_progress(1);
                         }
                     }
                 }
@@ -730,31 +1144,47 @@ impl Entity {
                 }
             }
         }
+        false
     }
 
     pub fn handle_gather(
         &mut self,
         goal: &mut GatherGoal,
+        building_container: &BuildingContainer,
         step_n: i32,
         step_delta: f32,
         event_handler: &mut EventHandler,
     ) {
+        if let Some(drop_off_building) = goal.drop_off_building {
+            if !goal.going_towards_resource
+                && building_container
+                    .get_building_by_id(drop_off_building)
+                    .is_none()
+            {
+                // The drop-off building was destroyed, find another one. The path is cleared so
+                // that this is requested only once.
+                goal.path = None;
+                goal.drop_off_building = None;
+                event_handler.add_event(Event::RequestGatherPath {
+                    entity_id: self.id,
+                    going_towards_resource: false,
+                    resource_position: goal.resource_position.clone(),
+                });
+            }
+        }
+
         if let Some(path_ref) = &goal.path {
             let path = path_ref.borrow();
             if goal.going_towards_resource {
-                if path.distance_to_goal(&self.position) < self.radius + 0.1 {
-                    // if self.distance_to_block(&goal.resource_position.as_vec2i()) < self.radius + 0.1 {
+                if path.distance_to_goal(&self.position) < self.definition.radius + 0.1 {
+                    // if self.distance_to_block(&goal.resource_position.as_vec2i()) < self.definition.radius + 0.1 {
                     if step_n == 0 {
                         goal.counter += 1;
                         if goal.counter > 200 {
-                            // TODO: Decrement map resource here
                             goal.counter = 0;
-                            goal.going_towards_resource = false;
 
-                            // println!("Requesting new path");
-                            event_handler.add_event(Event::RequestGatherPath {
+                            event_handler.add_event(Event::TakeResource {
                                 entity_id: self.id,
-                                going_towards_resource: false,
                                 resource_position: goal.resource_position.clone(),
                             })
                         }
@@ -770,12 +1200,12 @@ impl Entity {
                     }
                 }
             } else {
-                if path.distance_to_goal(&self.position) < self.radius + 0.1 {
+                if path.distance_to_goal(&self.position) < self.definition.radius + 0.1 {
                     // if let Some(building_data) = goal.building.clone() {
                     //     if self.distance_to_big_block(
                     //         &building_data.building_position,
                     //         &building_data.building_size,
-                    //     ) < self.radius + 0.1
+                    //     ) < self.definition.radius + 0.1
                     //     {
                     if step_n == 0 {
                         // TODO: This is a dropoff point
@@ -790,8 +1220,9 @@ impl Entity {
                         });
                         event_handler.add_event(Event::IncrementResources {
                             team: self.team,
-                            amounts: Resources::new(20),
+                            amounts: Resources::new_single(goal.resource_type, goal.carrying),
                         });
+                        goal.carrying = 0;
                     }
                 } else {
                     if let Some(path) = &goal.path {
@@ -811,10 +1242,13 @@ impl Entity {
         }
     }
 
+    #[allow(clippy::too_many_arguments)]
     pub fn update(
         &mut self,
         closest_enemy: Option<Rc<RefCell<Entity>>>,
         closest_enemy_building: Option<Rc<RefCell<Building>>>,
+        building_container: &BuildingContainer,
+        ground: &Ground,
         step_n: i32,
         step_delta: f32,
         event_handler: &mut EventHandler,
@@ -827,6 +1261,7 @@ impl Entity {
                 if let Some(closest_enemy) = closest_enemy {
                     self.interact_with_closest_enemy(
                         &closest_enemy,
+                        ground,
                         step_n,
                         step_delta,
                         true,
@@ -835,23 +1270,23 @@ impl Entity {
                 } else if let Some(closest_enemy_building) = closest_enemy_building {
                     self.interact_with_closest_enemy_building(
                         &closest_enemy_building,
+                        ground,
                         step_n,
                         step_delta,
                         true,
                         event_handler,
                     );
-                } else {
                 }
             }
             EntityAction::Move(goal) => {
                 if goal.path.borrow().distance_to_goal(&self.position)
-                    < (goal.group_size.sqrt() - (self.radius * 2.0)).max(0.1)
+                    < (goal.group_size.sqrt() - (self.definition.radius * 2.0)).max(0.1)
                 {
                     // println!(
                     //     "We are done with move {} {} {}",
                     //     goal.path.borrow().distance_to_goal(&self.position),
                     //     goal.group_size.sqrt(),
-                    //     self.radius,
+                    //     self.definition.radius,
                     // );
                     // if self.distance_to_goal(&goal.position) < 1.0 * (goal.group_size).sqrt() / 1.5 {
                     cloned_action = EntityAction::Idle;
@@ -862,7 +1297,7 @@ impl Entity {
             }
             EntityAction::Attack(goal) => {
                 if goal.path.borrow().distance_to_goal(&self.position)
-                    < (goal.group_size.sqrt() - (self.radius * 2.0)).max(0.1)
+                    < (goal.group_size.sqrt() - (self.definition.radius * 2.0)).max(0.1)
                 {
                     println!("We are done with attack move");
                     // if self.distance_to_goal(&goal.position) < 1.0 * (goal.group_size).sqrt() / 2.0 {
@@ -871,6 +1306,7 @@ impl Entity {
                     if let Some(closest_enemy) = closest_enemy {
                         self.interact_with_closest_enemy(
                             &closest_enemy,
+                            ground,
                             step_n,
                             step_delta,
                             true,
@@ -879,6 +1315,7 @@ impl Entity {
                     } else if let Some(closest_enemy_building) = closest_enemy_building {
                         self.interact_with_closest_enemy_building(
                             &closest_enemy_building,
+                            ground,
                             step_n,
                             step_delta,
                             true,
@@ -891,15 +1328,32 @@ impl Entity {
                 }
             }
             EntityAction::Gather(goal) => {
-                self.handle_gather(goal, step_n, step_delta, event_handler);
+                self.handle_gather(goal, building_container, step_n, step_delta, event_handler);
             }
             EntityAction::Build(goal) => {
-                self.handle_build(goal, step_n, step_delta, event_handler);
+                if self.handle_build(goal, building_container, step_n, step_delta, event_handler) {
+                    cloned_action = EntityAction::Idle;
+                }
+            }
+            EntityAction::AttackTarget(goal) => {
+                // The targeted entity is passed as the closest enemy
+                if self.handle_attack_target(
+                    goal,
+                    closest_enemy,
+                    building_container,
+                    ground,
+                    step_n,
+                    step_delta,
+                    event_handler,
+                ) {
+                    cloned_action = EntityAction::Idle;
+                }
             }
             EntityAction::Hold => {
                 if let Some(closest_enemy) = closest_enemy {
                     self.interact_with_closest_enemy(
                         &closest_enemy,
+                        ground,
                         step_n,
                         step_delta,
                         false,
@@ -908,12 +1362,12 @@ impl Entity {
                 } else if let Some(closest_enemy_building) = closest_enemy_building {
                     self.interact_with_closest_enemy_building(
                         &closest_enemy_building,
+                        ground,
                         step_n,
                         step_delta,
                         false,
                         event_handler,
                     );
-                } else {
                 }
             }
         }
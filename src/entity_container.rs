@@ -1,12 +1,13 @@
 use crate::entity::{Entity, EntityFilter};
+use crate::handle::{Handle, HandleStore};
 use crate::spacial_partition::{ObjectFilter, SpacialPartition};
-use crate::vec::{Vec2f, Vec2i};
+use crate::vec::Vec2f;
 use std::cell::RefCell;
-use std::collections::HashMap;
 use std::rc::Rc;
 
 pub struct EntityContainer {
     entities_rc: Vec<Rc<RefCell<Entity>>>,
+    entities_by_handle: HandleStore<Rc<RefCell<Entity>>>,
     spacial_partition: SpacialPartition<Entity, EntityFilter>,
     // entities_by_area: HashMap<(i32, i32), Vec<Rc<RefCell<Entity>>>>,
     // area_divider: u8,
@@ -14,39 +15,64 @@ pub struct EntityContainer {
 
 impl EntityContainer {
     pub fn new(entities: Vec<Entity>) -> EntityContainer {
-        let mut entities_rc: Vec<Rc<RefCell<Entity>>> = Vec::new();
+        let mut entity_container = EntityContainer {
+            entities_rc: Vec::new(),
+            entities_by_handle: HandleStore::new(),
+            spacial_partition: SpacialPartition::new(8),
+            // entities_by_area: HashMap::new(),
+            // area_divider: 8,
+        };
         for entity in entities {
-            entities_rc.push(Rc::new(RefCell::new(entity)));
+            entity_container.spawn_entity(entity);
+        }
+        entity_container
+    }
+
+    // Entities that already have their ids, e.g. from a save file
+    pub fn from_entities_rc(
+        entities_rc: Vec<Rc<RefCell<Entity>>>,
+        mut entities_by_handle: HandleStore<Rc<RefCell<Entity>>>,
+    ) -> EntityContainer {
+        for entity in entities_rc.iter() {
+            let id = entity.borrow().get_id();
+            entities_by_handle.restore(id, entity.clone());
         }
         EntityContainer {
             entities_rc,
+            entities_by_handle,
             spacial_partition: SpacialPartition::new(8),
-            // entities_by_area: HashMap::new(),
-            // area_divider: 8,
         }
     }
 
-    pub fn get_by_id(&mut self, entity_id: usize) -> &Rc<RefCell<Entity>> {
-        // TODO: Fetch this from hash map and do not iterate through all of the entities
-        self.entities_rc
-            .iter()
-            .filter(|e| e.borrow().get_id() == entity_id)
-            .next()
-            .unwrap()
+    pub fn get_handle_store(&self) -> &HandleStore<Rc<RefCell<Entity>>> {
+        &self.entities_by_handle
+    }
+
+    // None if the entity has died
+    pub fn get_by_id(&self, entity_id: Handle) -> Option<&Rc<RefCell<Entity>>> {
+        self.entities_by_handle.get(entity_id)
     }
 
-    pub fn spawn_entity(&mut self, entity: Entity) {
-        self.entities_rc.push(Rc::new(RefCell::new(entity)));
+    pub fn spawn_entity(&mut self, entity: Entity) -> Handle {
+        let entity_rc = Rc::new(RefCell::new(entity));
+        let id = self.entities_by_handle.insert(entity_rc.clone());
+        entity_rc.borrow_mut().set_id(id);
+        self.entities_rc.push(entity_rc);
+        id
     }
 
-    pub fn iter_alive(&self) -> std::slice::Iter<Rc<RefCell<Entity>>> {
-        return self.entities_rc.iter();
+    pub fn iter_alive(&self) -> std::slice::Iter<'_, Rc<RefCell<Entity>>> {
+        self.entities_rc.iter()
         // // TODO:
     }
 
     pub fn remove_dead(&mut self) {
+        let entities_by_handle = &mut self.entities_by_handle;
         self.entities_rc.retain(|entity_rc| {
             let entity = entity_rc.borrow();
+            if !entity.health.is_alive() {
+                entities_by_handle.remove(entity.get_id());
+            }
             entity.health.is_alive()
         });
     }
@@ -102,4 +128,24 @@ impl EntityContainer {
         self.spacial_partition
             .get_closest_object(position, max_radius, filter)
     }
+
+    // Closest entity that also matches the predicate, e.g. is not hidden behind walls. The
+    // predicate is checked from the closest entity on until one matches.
+    pub fn get_closest_entity_where(
+        &self,
+        position: Vec2f,
+        max_radius: f32,
+        filter: EntityFilter,
+        predicate: impl Fn(&Entity) -> bool,
+    ) -> Option<Rc<RefCell<Entity>>> {
+        let mut entities = self.entities_in_radius(position.clone(), max_radius, filter);
+        entities.sort_by(|a, b| {
+            let distance_a = (a.borrow().get_position() - position.clone()).length();
+            let distance_b = (b.borrow().get_position() - position.clone()).length();
+            distance_a.total_cmp(&distance_b)
+        });
+        entities
+            .into_iter()
+            .find(|entity| predicate(&entity.borrow()))
+    }
 }
@@ -0,0 +1,147 @@
+use crate::damage::{ArmorType, DamageType};
+use crate::resources::Resources;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+// Designers can tune units by editing this file, it is read when a game starts
+pub const ENTITY_TYPES_PATH: &str = "assets/entity_types.ron";
+
+// Name of a unit definition in the registry, e.g. "worker"
+#[derive(Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EntityType(String);
+
+impl EntityType {
+    pub fn new(name: &str) -> EntityType {
+        EntityType(name.to_string())
+    }
+
+    pub fn get_name(&self) -> &str {
+        &self.0
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ProjectileKind {
+    None, // Does not attack
+    Melee,
+    Arrow,
+    Boulder, // Slow, usually with a splash radius
+    Bolt,    // Flies to the end of the attack range, damaging everything it passes through
+}
+
+// How the unit is drawn
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum EntityShape {
+    Triangle,
+    Square,
+    Bar,
+    Diamond,
+    Cross,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct EntityDefinition {
+    pub entity_type: EntityType,
+    pub health: i32,
+    pub speed: f32,
+    pub radius: f32,
+    pub cost: Resources,
+    pub supply: i32,
+    pub build_time: i32,
+    pub sight_radius: f32, // How far the unit sees through the fog of war
+    pub attack_range: f32,
+    pub damage: i32,
+    pub damage_type: DamageType,
+    pub cooldown: i32,
+    pub splash_radius: f32,
+    pub friendly_fire: bool, // The splash also damages the own team
+    pub homing: bool,        // Projectiles follow the target instead of flying to where it was
+    pub armor: i32,          // Subtracted from every hit taken
+    pub armor_type: ArmorType,
+    pub projectile_kind: ProjectileKind,
+    pub is_worker: bool, // Can gather and construct buildings
+    pub shape: EntityShape,
+}
+
+// Game::new spawns the starting units with these types
+pub const REQUIRED_ENTITY_TYPES: [&str; 2] = ["melee", "ranged"];
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct EntityRegistry {
+    definitions: Vec<Rc<EntityDefinition>>,
+}
+
+impl Default for EntityRegistry {
+    // The definitions that were shipped with the game
+    fn default() -> Self {
+        EntityRegistry::parse(include_str!("../assets/entity_types.ron")).unwrap()
+    }
+}
+
+impl EntityRegistry {
+    pub fn parse(text: &str) -> io::Result<EntityRegistry> {
+        let entity_registry: EntityRegistry =
+            ron::from_str(text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        for name in REQUIRED_ENTITY_TYPES {
+            if entity_registry.get(&EntityType::new(name)).is_none() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("the unit type {} is missing", name),
+                ));
+            }
+        }
+        Ok(entity_registry)
+    }
+
+    pub fn load(path: &str) -> io::Result<EntityRegistry> {
+        EntityRegistry::parse(&fs::read_to_string(path)?)
+    }
+
+    // Falls back to the shipped definitions, e.g. when not running from the project directory
+    pub fn load_or_default(path: &str) -> EntityRegistry {
+        match EntityRegistry::load(path) {
+            Ok(entity_registry) => entity_registry,
+            Err(e) => {
+                println!(
+                    "Could not load {}, using default unit definitions: {}",
+                    path, e
+                );
+                EntityRegistry::default()
+            }
+        }
+    }
+
+    pub fn get(&self, entity_type: &EntityType) -> Option<&Rc<EntityDefinition>> {
+        self.definitions
+            .iter()
+            .find(|definition| definition.entity_type == *entity_type)
+    }
+
+    pub fn get_definitions(&self) -> &[Rc<EntityDefinition>] {
+        &self.definitions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_rejects_missing_starting_unit_types() {
+        assert!(EntityRegistry::parse("[]").is_err());
+        let default = EntityRegistry::default();
+        let without_ranged: Vec<_> = default
+            .get_definitions()
+            .iter()
+            .filter(|definition| definition.entity_type != EntityType::new("ranged"))
+            .map(|definition| (**definition).clone())
+            .collect();
+        let text = ron::to_string(&without_ranged).unwrap();
+        let error = EntityRegistry::parse(&text).err().unwrap();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
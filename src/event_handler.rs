@@ -1,19 +1,38 @@
-use crate::entity::EntityType;
+use crate::entity::AttackTarget;
+use crate::entity_registry::{EntityDefinition, ProjectileKind};
+use crate::handle::Handle;
+use crate::path_finder::PathGoal;
+use crate::projectile::ProjectilePayload;
 use crate::resources::Resources;
 use crate::vec::{Vec2f, Vec2i};
+use std::rc::Rc;
 
 pub enum Event {
     AddRangedProjectile {
         start: Vec2f,
         end: Vec2f,
+        kind: ProjectileKind,
+        payload: ProjectilePayload,
         team: u8,
+        homing_target: Option<AttackTarget>, // Set when it follows its target
     },
     AddMeleeProjectile {
         end: Vec2f,
+        payload: ProjectilePayload,
         team: u8,
     },
+    // A worker has finished mining a deposit
+    TakeResource {
+        entity_id: Handle,
+        resource_position: Vec2i,
+    },
+    // A worker has done one step of repairing a building, the team pays for it
+    RepairBuilding {
+        entity_id: Handle,
+        building_id: Handle,
+    },
     RequestGatherPath {
-        entity_id: usize,
+        entity_id: Handle,
         going_towards_resource: bool,
         resource_position: Vec2i,
     },
@@ -22,12 +41,17 @@ pub enum Event {
         amounts: Resources,
     },
     SpawnEntity {
-        entity_type: EntityType,
+        definition: Rc<EntityDefinition>,
         team: u8,
-        building_id: usize,
+        building_id: Handle,
     },
     RequestRePath {
-        entity_id: usize,
+        entity_id: Handle,
+    },
+    // The target of an attack has moved, find a path to where it is now
+    RequestAttackPath {
+        entity_id: Handle,
+        path_goal: PathGoal,
     },
 }
 
@@ -35,6 +59,12 @@ pub struct EventHandler {
     pub events: Vec<Event>,
 }
 
+impl Default for EventHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl EventHandler {
     pub fn new() -> EventHandler {
         EventHandler { events: Vec::new() }
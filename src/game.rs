@@ -1,24 +1,61 @@
 use crate::building::Building;
 use crate::building_container::BuildingContainer;
-use crate::camera::Camera;
-use crate::constants::{ENTITY_AMOUNT, GROUND_HEIGHT, GROUND_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH};
-use crate::draw::draw_health_bar;
-use crate::entity::{Entity, EntityFilter, EntityType};
+use crate::building_registry::{BuildingRegistry, BuildingType};
+use crate::checksum::StateHasher;
+use crate::command::{Command, RecordedCommand};
+use crate::constants::{
+    CANCEL_CONSTRUCTION_REFUND_PERCENT, ENTITY_AMOUNT, GATHER_AMOUNT, GROUND_HEIGHT, GROUND_WIDTH,
+    MAX_SPAWN_QUEUE_LENGTH, MAX_SUPPLY, SPLASH_EDGE_DAMAGE_PERCENT,
+};
+use crate::damage::calculate_damage;
+use crate::entity::{AttackTarget, Entity, EntityFilter};
 use crate::entity_container::EntityContainer;
+use crate::entity_registry::{EntityRegistry, EntityType};
 use crate::event_handler::{Event, EventHandler};
 use crate::ground::{Ground, GroundType};
-use crate::path_finder::{Path, PathFinder, PathGoal};
+use crate::handle::Handle;
+use crate::path_finder::{distance_to_big_block, Path, PathFinder, PathGoal};
+use crate::projectile::ProjectilePayload;
 use crate::projectile_handler::ProjectileHandler;
-use crate::resources::Resources;
+use crate::resources::{ResourceType, Resources};
+use crate::rng::{new_rng, GameRng};
+use crate::save::{PathTable, SavedEntity, SavedGame};
 use crate::team::Team;
 use crate::vec::{Vec2f, Vec2i};
+use crate::visibility::VisibilityGrid;
 use rand::Rng;
-use raqote::{DrawOptions, DrawTarget, PathBuilder, Point, SolidSource, Source};
 use std::cell::RefCell;
 use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::io;
 use std::rc::Rc;
 use std::time::Instant;
 
+// Why a building can not be placed somewhere
+#[derive(Debug, PartialEq)]
+pub enum PlacementError {
+    UnknownBuildingType,
+    OutOfBounds,
+    Blocked,  // A wall, resource or another building is in the way
+    TooClose, // Breaks the spacing rule of the building type
+}
+
+enum MoveGoalType {
+    Move,
+    Attack,
+    Gather(ResourceType),
+    Build(Rc<RefCell<Building>>),
+    AttackTarget(AttackTarget, PathGoal),
+}
+
+// Entity, closest enemy, close entities and closest enemy building
+type EntitySurroundings<'a> = (
+    &'a Rc<RefCell<Entity>>,
+    Option<Rc<RefCell<Entity>>>,
+    Vec<Rc<RefCell<Entity>>>,
+    Option<Rc<RefCell<Building>>>,
+);
+
 pub struct Game {
     entity_container: EntityContainer,
     building_container: BuildingContainer,
@@ -27,31 +64,63 @@ pub struct Game {
     path_finder: PathFinder,
     pub debug_path: Option<Rc<RefCell<Path>>>,
     teams: Vec<Team>,
+    seed: u64,
+    tick: u32,
+    command_queue: Vec<Command>,
+    command_log: Vec<RecordedCommand>,
+    entity_registry: EntityRegistry,
+    building_registry: BuildingRegistry,
+    rng: GameRng, // All randomness of the simulation must come from here to keep it deterministic
 }
 
 impl Game {
-    pub fn new() -> Game {
+    pub fn new(
+        seed: u64,
+        entity_registry: EntityRegistry,
+        building_registry: BuildingRegistry,
+    ) -> Game {
+        let mut rng = new_rng(seed);
+
+        let melee = entity_registry
+            .get(&EntityType::new("melee"))
+            .expect("EntityRegistry::parse checks for the melee unit type");
+        let ranged = entity_registry
+            .get(&EntityType::new("ranged"))
+            .expect("EntityRegistry::parse checks for the ranged unit type");
+        let town_hall = building_registry
+            .get(BuildingType::TownHall)
+            .expect("BuildingRegistry::parse checks for the town hall type");
+
         let mut entities: Vec<Entity> = Vec::new();
 
         // Spawn 10 entities at random positions in the range of -10, 10
         for _ in 0..ENTITY_AMOUNT {
-            let mut rng = rand::thread_rng();
             let x = rng.gen_range(1.0..GROUND_WIDTH as f32 - 1.0);
             let y = rng.gen_range(1.0..GROUND_HEIGHT as f32 - 1.0);
-            entities.push(Entity::new(Vec2f::new(x, y)));
+            let random_team = rng.gen::<u8>() % 2;
+            let random_definition = if rng.gen::<f32>() < 0.5 {
+                melee
+            } else {
+                ranged
+            };
+            entities.push(Entity::new(
+                Vec2f::new(x, y),
+                random_team,
+                random_definition.clone(),
+            ));
         }
 
         for i in 0..20 {
-            entities.push(Entity::new_params(
+            entities.push(Entity::new(
                 Vec2f::new(3.0 + i as f32 / 1000.0, 3.0 + i as f32 / 1000.0),
                 0,
-                EntityType::Melee,
+                melee.clone(),
             ))
         }
 
-        let mut entity_container = EntityContainer::new(entities);
+        let entity_container = EntityContainer::new(entities);
 
-        let mut ground = Ground::new();
+        let mut ground = Ground::new(&mut rng);
 
         for x in 0..10 {
             for y in 0..10 {
@@ -66,16 +135,23 @@ impl Game {
         for i in 0..5 {
             ground.set_at(4, 7 + i, GroundType::Gold);
             ground.set_at(GROUND_WIDTH - 9 + i, GROUND_HEIGHT - 4, GroundType::Gold);
+            ground.set_at(5 + i, 4, GroundType::Tree);
+            ground.set_at(GROUND_WIDTH - 4, GROUND_HEIGHT - 12 + i, GroundType::Tree);
+        }
+        for i in 0..2 {
+            ground.set_at(13, 5 + i, GroundType::Quarry);
+            ground.set_at(GROUND_WIDTH - 12, GROUND_HEIGHT - 7 + i, GroundType::Quarry);
         }
         let mut building_container = BuildingContainer::new();
-        building_container
-            .add_building(Building::new(Vec2i::new(8, 8), 2, 3, 0, true), &mut ground);
+        building_container.add_building(
+            Building::new(Vec2i::new(8, 8), 0, town_hall.clone(), true),
+            &mut ground,
+        );
         building_container.add_building(
             Building::new(
                 Vec2i::new(GROUND_WIDTH - 8, GROUND_HEIGHT - 8),
-                3,
-                2,
                 1,
+                town_hall.clone(),
                 true,
             ),
             &mut ground,
@@ -83,7 +159,7 @@ impl Game {
 
         let start_time = Instant::now();
 
-        let mut path_finder = PathFinder::new();
+        let path_finder = PathFinder::new();
 
         let total_time = start_time.elapsed().as_millis();
 
@@ -91,7 +167,7 @@ impl Game {
 
         let teams = vec![Team::new(0), Team::new(1)];
 
-        Game {
+        let mut game = Game {
             entity_container,
             building_container,
             ground,
@@ -99,658 +175,303 @@ impl Game {
             debug_path: None,
             path_finder,
             teams,
-        }
+            seed,
+            entity_registry,
+            building_registry,
+            tick: 0,
+            command_queue: Vec::new(),
+            command_log: Vec::new(),
+            rng,
+        };
+        game.recount_supply();
+        game.update_visibility();
+        game
     }
 
-    fn draw_projectiles(&self, dt: &mut DrawTarget, camera: &Camera) {
-        let mut path_builder = PathBuilder::new();
-
-        for projectile in self.projectile_handler.iter() {
-            let projectile_position = projectile.get_position();
-
-            let draw_pos =
-                camera.world_to_screen(&Vec2f::new(projectile_position.x, projectile_position.y));
-
-            path_builder.move_to(draw_pos.x, draw_pos.y);
-            path_builder.arc(
-                draw_pos.x,
-                draw_pos.y,
-                camera.length_to_pixels(0.1),
-                0.0,
-                2.0 * std::f32::consts::PI,
-            );
-        }
-
-        dt.fill(
-            &path_builder.finish(),
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(
-                255, 0x00, 0x00, 0x00,
-            )),
-            &DrawOptions::new(),
-        );
+    pub fn get_seed(&self) -> u64 {
+        self.seed
     }
 
-    fn draw_buildings(
-        &self,
-        dt: &mut DrawTarget,
-        camera: &Camera,
-        selected_building_id: &Option<usize>,
-    ) {
-        for building_ref in self.building_container.get_buildings().iter() {
-            let building = building_ref.borrow();
-
-            let mut path_builder = PathBuilder::new();
-            let mut selection_path_builder = PathBuilder::new();
-
-            let position = building.get_position();
-            let width = building.get_width();
-            let height = building.get_height();
-
-            let draw_pos = camera.world_to_screen(&Vec2f::new(
-                position.x as f32 + 0.1,
-                position.y as f32 + 0.1,
-            ));
-            path_builder.move_to(draw_pos.x, draw_pos.y);
-            path_builder.line_to(
-                draw_pos.x + camera.length_to_pixels_x(width as f32 - 0.2),
-                draw_pos.y,
-            );
-            path_builder.line_to(
-                draw_pos.x + camera.length_to_pixels_x(width as f32 - 0.2),
-                draw_pos.y + camera.length_to_pixels_y(height as f32 - 0.2),
-            );
-            path_builder.line_to(
-                draw_pos.x,
-                draw_pos.y + camera.length_to_pixels_y(height as f32 - 0.2),
-            );
-            path_builder.close();
-
-            let source = if building.get_team() == 0 {
-                Source::Solid(SolidSource::from_unpremultiplied_argb(
-                    255, 0x7d, 0xde, 0x92,
-                ))
-            } else {
-                Source::Solid(SolidSource::from_unpremultiplied_argb(
-                    255, 0xde, 0x7d, 0x92,
-                ))
-            };
+    pub fn get_entity_registry(&self) -> &EntityRegistry {
+        &self.entity_registry
+    }
 
-            if building.is_constructed() {
-                dt.fill(&path_builder.finish(), &source, &DrawOptions::new());
-            } else {
-                dt.stroke(
-                    &path_builder.finish(),
-                    &source,
-                    &mut raqote::StrokeStyle::default(),
-                    // stroke_style,
-                    &DrawOptions::new(),
-                    //     &selection_path,
-                    // &selection_source,
-                    // stroke_style,
-                    // &DrawOptions::new(),
-                );
-            }
+    pub fn get_building_registry(&self) -> &BuildingRegistry {
+        &self.building_registry
+    }
 
-            if let Some(selected_building_id) = selected_building_id {
-                if *selected_building_id == building.get_id() {
-                    let selection_draw_pos =
-                        camera.world_to_screen(&Vec2f::new(position.x as f32, position.y as f32));
-                    selection_path_builder.move_to(selection_draw_pos.x, selection_draw_pos.y);
-                    selection_path_builder.line_to(
-                        selection_draw_pos.x + camera.length_to_pixels_x(width as f32),
-                        selection_draw_pos.y,
-                    );
-                    selection_path_builder.line_to(
-                        selection_draw_pos.x + camera.length_to_pixels_x(width as f32),
-                        selection_draw_pos.y + camera.length_to_pixels_y(height as f32),
-                    );
-                    selection_path_builder.line_to(
-                        selection_draw_pos.x,
-                        selection_draw_pos.y + camera.length_to_pixels_y(height as f32),
-                    );
-                    selection_path_builder.close();
-
-                    let selection_path = selection_path_builder.finish();
-                    let selection_source =
-                        Source::Solid(SolidSource::from_unpremultiplied_argb(255, 0, 255, 0));
-
-                    let stroke_style = &mut raqote::StrokeStyle::default();
-                    dt.stroke(
-                        &selection_path,
-                        &selection_source,
-                        stroke_style,
-                        &DrawOptions::new(),
-                    );
-                }
-            }
+    pub fn get_tick(&self) -> u32 {
+        self.tick
+    }
 
-            let health_ratio = if building.is_constructed() {
-                building.health.health_ratio()
-            } else {
-                building.construction_progress.health_ratio()
-            };
-            draw_health_bar(
-                dt,
-                camera,
-                health_ratio,
-                &(building.get_position().as_vec2f()
-                    + Vec2f::new(
-                        building.get_width() as f32 / 2.0,
-                        building.get_height() as f32 + 0.1,
-                    )),
-                building.get_width() as f32,
-            );
-        }
+    // All commands applied so far, stamped with the tick they were applied on
+    pub fn get_command_log(&self) -> &[RecordedCommand] {
+        &self.command_log
     }
 
-    fn draw_entities(&self, dt: &mut DrawTarget, camera: &Camera, selected_entiy_ids: &Vec<usize>) {
-        let mut selection_path_builder = PathBuilder::new();
-        let mut goal_path = PathBuilder::new();
-        let mut entity_type_path_builder = PathBuilder::new();
-
-        let mut path_builder_0 = PathBuilder::new();
-        let mut path_builder_1 = PathBuilder::new();
-        let mut path_builder_2 = PathBuilder::new();
-        let mut path_builder_3 = PathBuilder::new();
-
-        let mut path_builders = [
-            &mut path_builder_0,
-            &mut path_builder_1,
-            &mut path_builder_2,
-            &mut path_builder_3,
-        ];
-
-        let (min_x, max_x, min_y, max_y) = self.get_draw_boundaries(camera);
-
-        for entity_ref in self.entity_container.entities_in_box(
-            Vec2f::new(min_x as f32, min_y as f32),
-            Vec2f::new(max_x as f32, max_y as f32),
-            EntityFilter::empty(),
-        ) {
+    // Hash of the simulation state. Two runs with the same seed and commands must end up
+    // with the same checksum, so this is used to detect desyncs.
+    pub fn state_checksum(&self) -> u64 {
+        let mut hasher = StateHasher::new();
+
+        for entity_ref in self.entity_container.iter_alive() {
             let entity = entity_ref.borrow();
-            let entity_position = entity.get_position();
-
-            let draw_pos =
-                camera.world_to_screen(&Vec2f::new(entity_position.x, entity_position.y));
-
-            let path_builder = &mut path_builders[entity.get_team() as usize];
-            let radius = entity.get_radius() - 0.0;
-
-            // path_builder.move_to(draw_pos.x, draw_pos.y);
-            // path_builder.arc(
-            //     draw_pos.x,
-            //     draw_pos.y,
-            //     camera.length_to_pixels(radius),
-            //     0.0,
-            //     2.0 * std::f32::consts::PI,
-            // );
-
-            // Draw an ellipsis
-            let delt_x = camera.length_to_pixels_x(radius);
-            let delt_y = camera.length_to_pixels_y(radius);
-            let cubic_rate = 0.55228; // https://stackoverflow.com/questions/1734745/how-to-create-circle-with-b%C3%A9zier-curves
-            path_builder.move_to(draw_pos.x, draw_pos.y - delt_y);
-            path_builder.cubic_to(
-                draw_pos.x + delt_x * cubic_rate,
-                draw_pos.y - delt_y,
-                draw_pos.x + delt_x,
-                draw_pos.y - delt_y * cubic_rate,
-                draw_pos.x + delt_x,
-                draw_pos.y,
-            );
-            path_builder.cubic_to(
-                draw_pos.x + delt_x,
-                draw_pos.y + delt_y * cubic_rate,
-                draw_pos.x + delt_x * cubic_rate,
-                draw_pos.y + delt_y,
-                draw_pos.x,
-                draw_pos.y + delt_y,
-            );
-            path_builder.cubic_to(
-                draw_pos.x - delt_x * cubic_rate,
-                draw_pos.y + delt_y,
-                draw_pos.x - delt_x,
-                draw_pos.y + delt_y * cubic_rate,
-                draw_pos.x - delt_x,
-                draw_pos.y,
-            );
-            path_builder.cubic_to(
-                draw_pos.x - delt_x,
-                draw_pos.y - delt_y * cubic_rate,
-                draw_pos.x - delt_x * cubic_rate,
-                draw_pos.y - delt_y,
-                draw_pos.x,
-                draw_pos.y - delt_y,
-            );
-            // path_builder.quad_to( draw_pos.x + delt_x, draw_pos.y + delt_y, draw_pos.x, draw_pos.y + delt_y, );
-
-            // path_builder.quad_to( draw_pos.x - delt_x, draw_pos.y, draw_pos.x, draw_pos.y - delt_y, );
-            path_builder.close();
-
-            let delt_x = camera.length_to_pixels_x(radius) * 0.5;
-            let delt_y = camera.length_to_pixels_y(radius) * 0.5;
-
-            match entity.get_entity_type() {
-                EntityType::Ranged => {
-                    entity_type_path_builder.move_to(draw_pos.x, draw_pos.y - delt_y);
-                    entity_type_path_builder
-                        .line_to(draw_pos.x + delt_x * 0.81, draw_pos.y + delt_y * 0.58);
-                    entity_type_path_builder
-                        .line_to(draw_pos.x - delt_x * 0.81, draw_pos.y + delt_y * 0.58);
-                    entity_type_path_builder.close();
-                }
-                EntityType::Melee => {
-                    entity_type_path_builder
-                        .move_to(draw_pos.x - delt_x * 0.707, draw_pos.y - delt_y * 0.707);
-                    entity_type_path_builder
-                        .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y - delt_y * 0.707);
-                    entity_type_path_builder
-                        .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y + delt_y * 0.707);
-                    entity_type_path_builder
-                        .line_to(draw_pos.x - delt_x * 0.707, draw_pos.y + delt_y * 0.707);
-                    entity_type_path_builder.close();
-                }
-                EntityType::Worker => {
-                    entity_type_path_builder
-                        .move_to(draw_pos.x - delt_x * 0.707, draw_pos.y - delt_y * 0.2);
-                    entity_type_path_builder
-                        .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y - delt_y * 0.2);
-                    entity_type_path_builder
-                        .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y + delt_y * 0.2);
-                    entity_type_path_builder
-                        .line_to(draw_pos.x - delt_x * 0.707, draw_pos.y + delt_y * 0.2);
-                    entity_type_path_builder.close();
-                }
+            entity.get_id().hash(&mut hasher);
+            entity.get_team().hash(&mut hasher);
+            entity.get_position().x.to_bits().hash(&mut hasher);
+            entity.get_position().y.to_bits().hash(&mut hasher);
+            entity.health.get_health().hash(&mut hasher);
+        }
+        for building_ref in self.building_container.get_buildings().iter() {
+            let building = building_ref.borrow();
+            building.get_id().hash(&mut hasher);
+            building.get_spawn_timer().hash(&mut hasher);
+            building.get_spawn_queue().len().hash(&mut hasher);
+            building.health.get_health().hash(&mut hasher);
+            building
+                .construction_progress
+                .get_health()
+                .hash(&mut hasher);
+        }
+        for projectile in self.projectile_handler.iter() {
+            projectile.get_position().x.to_bits().hash(&mut hasher);
+            projectile.get_position().y.to_bits().hash(&mut hasher);
+        }
+        for team in self.teams.iter() {
+            team.get_resources().hash(&mut hasher);
+            team.get_supply_used().hash(&mut hasher);
+            team.get_supply_cap().hash(&mut hasher);
+        }
+        // Gathering changes what is left of the resources
+        for y in 0..self.ground.get_height() {
+            for x in 0..self.ground.get_width() {
+                self.ground.get_resource_amount_at(x, y).hash(&mut hasher);
             }
+        }
 
-            if selected_entiy_ids.contains(&entity.get_id()) {
-                if let Some(goal) = entity.get_goal() {
-                    let goal_pos = camera.world_to_screen(&Vec2f::new(goal.x, goal.y));
+        hasher.finish()
+    }
 
-                    goal_path.move_to(draw_pos.x, draw_pos.y);
-                    goal_path.line_to(goal_pos.x, goal_pos.y);
-                }
+    pub fn to_saved(&self) -> SavedGame {
+        let mut path_table = PathTable::default();
+        let entities = self
+            .entity_container
+            .iter_alive()
+            .map(|entity| SavedEntity {
+                entity: entity.clone(),
+                action: entity.borrow().get_saved_action(&mut path_table),
+            })
+            .collect();
 
-                let top_right_corner = camera.world_to_screen(&Vec2f::new(
-                    entity_position.x - radius,
-                    entity_position.y - radius,
-                ));
+        SavedGame {
+            seed: self.seed,
+            tick: self.tick,
+            entity_registry: self.entity_registry.clone(),
+            building_registry: self.building_registry.clone(),
+            rng: self.rng.clone(),
+            command_queue: self.command_queue.clone(),
+            command_log: self.command_log.clone(),
+            ground: self.ground.clone(),
+            teams: self.teams.clone(),
+            paths: path_table.into_paths(),
+            buildings: self.building_container.get_buildings().clone(),
+            building_handles: self.building_container.get_handle_store().clone(),
+            entities,
+            entity_handles: self.entity_container.get_handle_store().clone(),
+            projectile_handler: self.projectile_handler.clone(),
+        }
+    }
 
-                selection_path_builder.rect(
-                    top_right_corner.x,
-                    top_right_corner.y,
-                    camera.length_to_pixels_x(radius * 2.0),
-                    camera.length_to_pixels_y(radius * 2.0),
-                );
-            }
+    pub fn from_saved(saved_game: SavedGame) -> io::Result<Game> {
+        // Buildings have to exist before entity actions can refer to them
+        let building_container =
+            BuildingContainer::from_buildings(saved_game.buildings, saved_game.building_handles);
+
+        let mut entities_rc: Vec<Rc<RefCell<Entity>>> = Vec::new();
+        for saved_entity in saved_game.entities {
+            saved_entity
+                .entity
+                .borrow_mut()
+                .restore_action(saved_entity.action, &saved_game.paths)?;
+            entities_rc.push(saved_entity.entity);
         }
 
-        dt.fill(
-            &path_builder_0.finish(),
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(
-                255, 0x7d, 0xde, 0x92,
-            )),
-            &DrawOptions::new(),
-        );
-        dt.fill(
-            &path_builder_1.finish(),
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(
-                255, 0xde, 0x7d, 0x92,
-            )),
-            &DrawOptions::new(),
-        );
-        dt.fill(
-            &path_builder_2.finish(),
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(
-                255, 0xde, 0x92, 0x7d,
-            )),
-            &DrawOptions::new(),
-        );
-        dt.fill(
-            &path_builder_3.finish(),
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(
-                255, 0x92, 0xde, 0x7d,
-            )),
-            &DrawOptions::new(),
-        );
+        // The spatial partition is not saved
+        let mut entity_container =
+            EntityContainer::from_entities_rc(entities_rc, saved_game.entity_handles);
+        entity_container.update_entities_by_area();
 
-        let selection_path = selection_path_builder.finish();
-        let selection_source =
-            Source::Solid(SolidSource::from_unpremultiplied_argb(255, 0, 255, 0));
+        Ok(Game {
+            entity_container,
+            building_container,
+            ground: saved_game.ground,
+            projectile_handler: saved_game.projectile_handler,
+            path_finder: PathFinder::new(),
+            debug_path: None,
+            teams: saved_game.teams,
+            seed: saved_game.seed,
+            tick: saved_game.tick,
+            command_queue: saved_game.command_queue,
+            command_log: saved_game.command_log,
+            entity_registry: saved_game.entity_registry,
+            building_registry: saved_game.building_registry,
+            rng: saved_game.rng,
+        })
+    }
 
-        let stroke_style = &mut raqote::StrokeStyle::default();
-        dt.stroke(
-            &selection_path,
-            &selection_source,
-            stroke_style,
-            &DrawOptions::new(),
-        );
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        self.to_saved().save(path)
+    }
 
-        dt.stroke(
-            &goal_path.finish(),
-            &selection_source,
-            stroke_style,
-            &DrawOptions::new(),
-        );
+    pub fn load(path: &str) -> io::Result<Game> {
+        Game::from_saved(SavedGame::load(path)?)
+    }
 
-        let entity_type_path = entity_type_path_builder.finish();
-        dt.fill(
-            &entity_type_path,
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
-            // stroke_style,
-            &DrawOptions::new(),
-        );
+    pub fn get_entity_container(&self) -> &EntityContainer {
+        &self.entity_container
+    }
 
-        for entity_ref in self.entity_container.iter_alive() {
-            let entity = entity_ref.borrow();
-            let entity_position = entity.get_position();
-
-            let health_ratio = entity.health.health_ratio();
-            if health_ratio < 1.0 {
-                draw_health_bar(
-                    dt,
-                    camera,
-                    health_ratio,
-                    &(entity_position + Vec2f::new(0.0, entity.get_radius() + 0.1)),
-                    entity.get_radius() * 2.0,
-                );
-            }
-        }
+    pub fn get_building_container(&self) -> &BuildingContainer {
+        &self.building_container
     }
 
-    fn get_draw_boundaries(&self, camera: &Camera) -> (i32, i32, i32, i32) {
-        let top_left = camera.screen_to_world(&Vec2f::new(0.0, 0.0));
-        let bottom_right =
-            camera.screen_to_world(&Vec2f::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32));
+    pub fn get_projectile_handler(&self) -> &ProjectileHandler {
+        &self.projectile_handler
+    }
 
-        let min_x = 0.max(top_left.x as i32);
-        let max_x = self.ground.get_width().min(bottom_right.x as i32 + 1);
-        let min_y = 0.max(top_left.y as i32);
-        let max_y = self.ground.get_height().min(bottom_right.y as i32 + 1);
+    pub fn get_teams(&self) -> &[Team] {
+        &self.teams
+    }
 
-        (min_x, max_x, min_y, max_y)
+    pub fn get_team_visibility(&self, team_id: u8) -> Option<&VisibilityGrid> {
+        self.teams
+            .iter()
+            .find(|team| team.get_id() == team_id)
+            .map(|team| team.get_visibility())
     }
 
-    fn draw_ground(&self, dt: &mut DrawTarget, camera: &Camera) {
-        let mut ground_path_builder = PathBuilder::new();
-        let mut wall_path_builder = PathBuilder::new();
-        let mut gold_path_builder = PathBuilder::new();
-
-        let (min_x, max_x, min_y, max_y) = self.get_draw_boundaries(camera);
-
-        for x in min_x..max_x {
-            for y in min_y..max_y {
-                let ground_type = self.ground.get_at(x, y);
-                match ground_type {
-                    GroundType::Empty | GroundType::Gold => {
-                        let draw_pos = camera.world_to_screen(&Vec2f::new(x as f32, y as f32));
-                        ground_path_builder.move_to(draw_pos.x, draw_pos.y);
-                        ground_path_builder
-                            .line_to(draw_pos.x + camera.length_to_pixels_x(1.0), draw_pos.y);
-                        ground_path_builder.line_to(
-                            draw_pos.x + camera.length_to_pixels_x(1.0),
-                            draw_pos.y + camera.length_to_pixels_y(1.0),
-                        );
-                        ground_path_builder
-                            .line_to(draw_pos.x, draw_pos.y + camera.length_to_pixels_y(1.0));
-                        ground_path_builder.close();
-                    }
-                    GroundType::Wall => {
-                        let draw_pos = camera.world_to_screen(&Vec2f::new(x as f32, y as f32));
-                        wall_path_builder.move_to(draw_pos.x, draw_pos.y);
-                        wall_path_builder
-                            .line_to(draw_pos.x + camera.length_to_pixels_x(1.0), draw_pos.y);
-                        wall_path_builder.line_to(
-                            draw_pos.x + camera.length_to_pixels_x(1.0),
-                            draw_pos.y + camera.length_to_pixels_y(1.0),
-                        );
-                        wall_path_builder
-                            .line_to(draw_pos.x, draw_pos.y + camera.length_to_pixels_y(1.0));
-                        wall_path_builder.close();
-                    }
-                }
-                match ground_type {
-                    GroundType::Gold => {
-                        for (xx, yy) in [
-                            // (0.15, 0.0),
-                            // (0.85, 0.0),
-                            // (0.15, 0.7),
-                            // (0.85, 0.7),
-                            (0.15 + 0.2, 0.0 + 0.1),
-                            (0.85 - 0.1, 0.0 + 0.2),
-                            (0.15 + 0.2, 0.7 - 0.3),
-                            (0.85 - 0.2, 0.7 - 0.1),
-                        ] {
-                            let draw_pos =
-                                camera.world_to_screen(&Vec2f::new(x as f32 + xx, y as f32 + yy));
-                            gold_path_builder.move_to(draw_pos.x, draw_pos.y);
-                            gold_path_builder.line_to(
-                                draw_pos.x + camera.length_to_pixels_x(0.15),
-                                draw_pos.y + camera.length_to_pixels_y(0.3),
-                            );
-                            gold_path_builder.line_to(
-                                draw_pos.x - camera.length_to_pixels_x(0.15),
-                                draw_pos.y + camera.length_to_pixels_y(0.3),
-                            );
-                            // gold_path_builder.line_to(draw_pos.x, draw_pos.y + camera.length_to_pixels(1.0));
-                            gold_path_builder.close();
-                        }
-                    }
-                    _ => {}
-                }
-            }
+    // Whether the team sees the entity. Everything is seen when no team is given.
+    pub fn is_entity_seen_by(&self, entity: &Entity, seen_by: Option<u8>) -> bool {
+        match seen_by {
+            Some(team_id) => self
+                .get_team_visibility(team_id)
+                .is_some_and(|visibility| visibility.is_visible(&entity.get_position())),
+            None => true,
         }
+    }
 
-        let ground_path = ground_path_builder.finish();
-        let wall_path = wall_path_builder.finish();
-        let wall_source = Source::Solid(SolidSource::from_unpremultiplied_argb(
-            255, 0x89, 0x99, 0xa6,
-        ));
-        let ground_source = Source::Solid(SolidSource::from_unpremultiplied_argb(
-            255, 0x48, 0x40, 0x41,
-        ));
-        let gold_source = Source::Solid(SolidSource::from_unpremultiplied_argb(
-            255, 0xff, 0xd7, 0x00,
-        ));
-
-        dt.fill(&ground_path, &ground_source, &DrawOptions::new());
-        dt.fill(&wall_path, &wall_source, &DrawOptions::new());
-        dt.fill(
-            &gold_path_builder.finish(),
-            &gold_source,
-            &DrawOptions::new(),
-        );
+    // Whether the team sees any part of the building. Everything is seen when no team is given.
+    pub fn is_building_seen_by(&self, building: &Building, seen_by: Option<u8>) -> bool {
+        match seen_by {
+            Some(team_id) => self
+                .get_team_visibility(team_id)
+                .is_some_and(|visibility| is_building_visible(visibility, building)),
+            None => true,
+        }
     }
 
-    pub fn draw_debug_path(&self, dt: &mut DrawTarget, camera: &Camera) {
-        if let Some(debug_path) = &self.debug_path {
-            let mut path_builder = PathBuilder::new();
-
-            let (min_x, max_x, min_y, max_y) = self.get_draw_boundaries(camera);
-
-            let debug_path = debug_path.borrow();
-            for x in min_x..max_x {
-                for y in min_y..max_y {
-                    let path_item = (x, y);
-                    if let Some(direction) = debug_path.position_datas.get(&path_item) {
-                        let center_pos = camera.world_to_screen(&Vec2f::new(
-                            path_item.0 as f32 + 0.5,
-                            path_item.1 as f32 + 0.5,
-                        ));
-
-                        path_builder.move_to(
-                            center_pos.x - camera.length_to_pixels_x(direction.x * 0.2),
-                            center_pos.y - camera.length_to_pixels_y(direction.y * 0.2),
-                        );
-                        path_builder.line_to(
-                            center_pos.x + camera.length_to_pixels_x(direction.x * 0.2),
-                            center_pos.y + camera.length_to_pixels_y(direction.y * 0.2),
-                        );
-                        path_builder.line_to(
-                            center_pos.x
-                                + camera.length_to_pixels_x(
-                                    direction.x * 0.2 + direction.y * 0.1 - direction.x * 0.1,
-                                ),
-                            center_pos.y
-                                + camera.length_to_pixels_y(
-                                    direction.y * 0.2 - direction.x * 0.1 - direction.y * 0.1,
-                                ),
-                        );
-                        path_builder.move_to(
-                            center_pos.x + camera.length_to_pixels_x(direction.x * 0.2),
-                            center_pos.y + camera.length_to_pixels_y(direction.y * 0.2),
-                        );
-                        path_builder.line_to(
-                            center_pos.x
-                                + camera.length_to_pixels_x(
-                                    direction.x * 0.2 - direction.y * 0.1 - direction.x * 0.1,
-                                ),
-                            center_pos.y
-                                + camera.length_to_pixels_y(
-                                    direction.y * 0.2 + direction.x * 0.1 - direction.y * 0.1,
-                                ),
-                        );
-                    }
+    // Every team sees what its units and buildings see, what was seen before stays explored
+    fn update_visibility(&mut self) {
+        for team in self.teams.iter_mut() {
+            let team_id = team.get_id();
+            let visibility = team.get_visibility_mut();
+            visibility.start_update();
+            for entity in self.entity_container.iter_alive() {
+                let entity = entity.borrow();
+                if entity.get_team() != team_id {
+                    continue;
+                }
+                let position = entity.get_position();
+                visibility.reveal(
+                    &self.ground,
+                    &position,
+                    &position,
+                    entity.get_definition().sight_radius,
+                );
+            }
+            for building in self.building_container.get_buildings().iter() {
+                let building = building.borrow();
+                if building.get_team() != team_id {
+                    continue;
                 }
+                let top_left = building.get_position().as_vec2f() + Vec2f::new(0.5, 0.5);
+                let bottom_right = top_left.clone()
+                    + Vec2f::new(
+                        building.get_width() as f32 - 1.0,
+                        building.get_height() as f32 - 1.0,
+                    );
+                visibility.reveal(
+                    &self.ground,
+                    &top_left,
+                    &bottom_right,
+                    building.get_definition().sight_radius,
+                );
             }
-
-            let path = path_builder.finish();
-            let source = Source::Solid(SolidSource::from_unpremultiplied_argb(
-                255, 0x38, 0x30, 0x31,
-            ));
-            let stroke_style = &mut raqote::StrokeStyle::default();
-            dt.stroke(&path, &source, &stroke_style, &DrawOptions::new());
+            visibility.remember_ground(&self.ground);
+            visibility.remember_buildings(self.building_container.get_buildings(), team_id);
         }
     }
 
-    pub fn draw(
-        &self,
-        dt: &mut DrawTarget,
-        camera: &Camera,
-        selected_entiy_ids: &Vec<usize>,
-        selected_building_id: &Option<usize>,
-    ) {
-        self.draw_ground(dt, camera);
-        self.draw_entities(dt, camera, selected_entiy_ids);
-        self.draw_buildings(dt, camera, selected_building_id);
-        self.draw_projectiles(dt, camera);
-        self.draw_debug_path(dt, camera);
-        self.draw_overlay(dt, camera, selected_entiy_ids, selected_building_id);
-    }
-
-    pub fn draw_overlay(
-        &self,
-        dt: &mut DrawTarget,
-        camera: &Camera,
-        selected_entiy_ids: &Vec<usize>,
-        selected_building_id: &Option<usize>,
-    ) {
-        let font = font_kit::loader::Loader::from_file(
-            &mut std::fs::File::open("res/Roboto-Medium.ttf").unwrap(),
-            0,
-        )
-        .unwrap();
-
-        for (i, team) in self.teams.iter().enumerate() {
-            dt.draw_text(
-                &font,
-                20.,
-                &format!("Team {} Gold: {}", team.get_id(), team.get_resources().gold),
-                // "3",
-                Point::new(0., 20. + 20. * i as f32),
-                &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
-                &DrawOptions::new(),
-            );
+    // Targets that went into the fog of war can not be followed or shot at. Their attackers
+    // attack-move to where the target was when it went out of sight instead, or stand idle when
+    // there is no path there.
+    fn stop_attacks_into_fog(&mut self) {
+        let mut lost_targets: Vec<(AttackTarget, Vec2f, Vec<Handle>)> = Vec::new();
+        for entity in self.entity_container.iter_alive() {
+            let entity = entity.borrow();
+            let target = match entity.get_attack_target() {
+                Some(target) => target,
+                None => continue,
+            };
+            let visibility = match self.get_team_visibility(entity.get_team()) {
+                Some(visibility) => visibility,
+                None => continue,
+            };
+            // A dead target is noticed by the attacker itself
+            let hidden_position = match target {
+                AttackTarget::Entity(target_id) => self
+                    .entity_container
+                    .get_by_id(target_id)
+                    .map(|target| target.borrow().get_position())
+                    .filter(|position| !visibility.is_visible(position)),
+                AttackTarget::Building(building_id) => self
+                    .building_container
+                    .get_building_by_id(building_id)
+                    .filter(|building| !is_building_visible(visibility, &building.borrow()))
+                    .map(|building| building.borrow().get_center_position()),
+            };
+            let position = match hidden_position {
+                Some(position) => position,
+                None => continue,
+            };
+            match lost_targets.iter_mut().find(|(lost, _, _)| *lost == target) {
+                Some((_, _, entity_ids)) => entity_ids.push(entity.get_id()),
+                None => lost_targets.push((target, position, vec![entity.get_id()])),
+            }
         }
 
-        dt.fill_rect(
-            0.,
-            SCREEN_HEIGHT as f32 - 180.,
-            SCREEN_WIDTH as f32,
-            180.,
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 128, 128, 128)),
-            &DrawOptions::new(),
-        );
-
-        dt.fill_rect(
-            10.,
-            SCREEN_HEIGHT as f32 - 170.,
-            160.,
-            160.,
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 64, 64, 64)),
-            &DrawOptions::new(),
-        );
-
-        dt.draw_text(
-            &font,
-            20.,
-            &format!("Minimap here"),
-            Point::new(10., SCREEN_HEIGHT as f32 - 170. + 20.),
-            &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
-            &DrawOptions::new(),
-        );
-
-        if let Some(selected_building_id) = selected_building_id {
-            if let Some(building_ref) = self
-                .building_container
-                .get_building_by_id(*selected_building_id)
-            {
-                let building = building_ref.borrow();
-
-                if building.is_constructed() {
-                    let spawn_queue = building.get_spawn_queue().clone();
-                    let spawn_timer = building.get_spawn_timer();
-                    for (i, spawn_item) in spawn_queue.iter().enumerate() {
-                        dt.draw_text(
-                            &font,
-                            20.,
-                            &format!("{:?}", spawn_item),
-                            Point::new(180., SCREEN_HEIGHT as f32 - 170. + 20. + 20. * i as f32),
-                            &Source::Solid(SolidSource::from_unpremultiplied_argb(
-                                255, 255, 255, 255,
-                            )),
-                            &DrawOptions::new(),
-                        );
-                    }
-                    if !spawn_queue.is_empty() {
-                        let spawn_duratoin = building.get_spawn_duration();
-                        dt.fill_rect(
-                            180. + 100.,
-                            SCREEN_HEIGHT as f32 - 170.,
-                            100.,
-                            20.,
-                            &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 64, 64, 64)),
-                            &DrawOptions::new(),
-                        );
-                        dt.fill_rect(
-                            180. + 100.,
-                            SCREEN_HEIGHT as f32 - 170.,
-                            100. * (spawn_timer as f32 / spawn_duratoin as f32),
-                            20.,
-                            &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 0, 255, 0)),
-                            &DrawOptions::new(),
-                        );
-                    }
-                } else {
-                    dt.draw_text(
-                        &font,
-                        20.,
-                        &format!(
-                            "Under construction: {}%",
-                            (building.construction_progress.health_ratio() * 100.0) as i32
-                        ),
-                        Point::new(180., SCREEN_HEIGHT as f32 - 170. + 20. + 20.),
-                        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
-                        &DrawOptions::new(),
-                    );
+        for (_, position, entity_ids) in lost_targets {
+            for entity_id in entity_ids.iter() {
+                if let Some(entity) = self.entity_container.get_by_id(*entity_id) {
+                    entity.borrow_mut().set_action_idle();
                 }
             }
+            self.command_entities_move(&entity_ids, &position, MoveGoalType::Attack);
         }
     }
 
-    pub fn entity_ids_in_bounding_box(&self, top_left: &Vec2f, bottom_right: &Vec2f) -> Vec<usize> {
-        let mut entity_ids: Vec<usize> = Vec::new();
+    // Entities hidden from the team are left out
+    pub fn entity_ids_in_bounding_box(
+        &self,
+        top_left: &Vec2f,
+        bottom_right: &Vec2f,
+        seen_by: Option<u8>,
+    ) -> Vec<Handle> {
+        let mut entity_ids: Vec<Handle> = Vec::new();
         for entity in self.entity_container.iter_alive() {
+            if !self.is_entity_seen_by(&entity.borrow(), seen_by) {
+                continue;
+            }
             let entity_position = entity.borrow().get_position();
             if entity_position.x >= top_left.x
                 && entity_position.x <= bottom_right.x
@@ -763,12 +484,17 @@ impl Game {
         entity_ids
     }
 
+    // Buildings hidden from the team are left out
     pub fn first_building_id_in_bouding_box(
         &self,
         top_left: &Vec2f,
         bottom_right: &Vec2f,
-    ) -> Option<usize> {
+        seen_by: Option<u8>,
+    ) -> Option<Handle> {
         for building in self.building_container.get_buildings().iter() {
+            if !self.is_building_seen_by(&building.borrow(), seen_by) {
+                continue;
+            }
             let pos = building.borrow().get_position().as_vec2f();
             let width = building.borrow().get_width() as f32;
             let height = building.borrow().get_height() as f32;
@@ -784,6 +510,180 @@ impl Game {
         None
     }
 
+    // Supply is counted from scratch so that deaths, destroyed farms and cancelled queue items
+    // never have to be tracked one by one
+    fn recount_supply(&mut self) {
+        for team in self.teams.iter_mut() {
+            let mut supply_used = 0;
+            let mut supply_cap = 0;
+            for entity in self.entity_container.iter_alive() {
+                let entity = entity.borrow();
+                if entity.get_team() == team.get_id() {
+                    supply_used += entity.get_definition().supply;
+                }
+            }
+            for building in self.building_container.get_buildings().iter() {
+                let building = building.borrow();
+                if building.get_team() != team.get_id() {
+                    continue;
+                }
+                for definition in building.get_spawn_queue().iter() {
+                    supply_used += definition.supply;
+                }
+                if building.is_constructed() {
+                    supply_cap += building.get_definition().supply_provided;
+                }
+            }
+            team.set_supply(supply_used, supply_cap.min(MAX_SUPPLY));
+        }
+    }
+
+    // Projectiles move and hit what they touch on the way or where they land
+    fn update_projectiles(&mut self) {
+        // Homing projectiles follow their target while it is alive
+        for projectile in self.projectile_handler.iter_mut() {
+            let target_position = match projectile.get_homing_target() {
+                Some(AttackTarget::Entity(entity_id)) => self
+                    .entity_container
+                    .get_by_id(entity_id)
+                    .map(|entity| entity.borrow().get_position()),
+                Some(AttackTarget::Building(building_id)) => self
+                    .building_container
+                    .get_building_by_id(building_id)
+                    .map(|building| building.borrow().get_center_position()),
+                None => None,
+            };
+            if let Some(target_position) = target_position {
+                projectile.set_goal(target_position);
+            }
+        }
+
+        self.projectile_handler
+            .progress_projectiles(&self.ground, &self.building_container);
+
+        // Arrows hit the first enemy they touched on the way and bolts all of them
+        for projectile in self
+            .projectile_handler
+            .iter_mut()
+            .filter(|projectile| projectile.hits_on_the_way())
+        {
+            let team = projectile.get_team();
+            let mut entities_hit: Vec<Rc<RefCell<Entity>>> = self
+                .entity_container
+                .entities_in_radius(
+                    projectile.get_position(),
+                    1.0, // More than a projectile moves plus the radius of any unit
+                    EntityFilter::not_team(team),
+                )
+                .into_iter()
+                .filter(|entity| {
+                    let entity = entity.borrow();
+                    projectile.passed_through(&entity.get_position(), entity.get_radius())
+                        && !projectile.has_hit(&AttackTarget::Entity(entity.get_id()))
+                })
+                .collect();
+            entities_hit.sort_by(|entity1, entity2| {
+                let distance1 = projectile.distance_travelled_to(&entity1.borrow().get_position());
+                let distance2 = projectile.distance_travelled_to(&entity2.borrow().get_position());
+                distance1.total_cmp(&distance2)
+            });
+
+            let mut is_stopped_by_entity = false;
+            for entity_hit in entities_hit {
+                damage_entity(&entity_hit, projectile.get_payload(), 100);
+                projectile.add_hit(AttackTarget::Entity(entity_hit.borrow().get_id()));
+                if !projectile.is_piercing() {
+                    projectile.stop();
+                    is_stopped_by_entity = true;
+                    break;
+                }
+            }
+
+            // A wall or an enemy building stopped it
+            if let Some(tile) = projectile.get_blocked_at() {
+                if is_stopped_by_entity {
+                    continue;
+                }
+                if let Some(building_hit) =
+                    self.building_container
+                        .get_building_on_tile(&tile, None, Some(team))
+                {
+                    let target = AttackTarget::Building(building_hit.borrow().get_id());
+                    if !projectile.has_hit(&target) {
+                        damage_building(&building_hit, projectile.get_payload(), 100);
+                        projectile.add_hit(target);
+                    }
+                }
+            }
+        }
+
+        for projectile in self.projectile_handler.get_impacting_projectiles() {
+            let position = projectile.get_position();
+            let payload = projectile.get_payload();
+            if projectile.hits_on_the_way() {
+                // It has hit something on the way or missed
+                continue;
+            }
+
+            if payload.splash_radius > 0.0 {
+                // Damage falls off from the center to the edge of the splash
+                let not_team = if payload.friendly_fire {
+                    None
+                } else {
+                    Some(projectile.get_team())
+                };
+                let falloff_percent = |distance: f32| {
+                    100 - ((100 - SPLASH_EDGE_DAMAGE_PERCENT) as f32 * distance
+                        / payload.splash_radius) as i32
+                };
+                let filter = match not_team {
+                    Some(not_team) => EntityFilter::not_team(not_team),
+                    None => EntityFilter::empty(),
+                };
+                for entity_hit in self.entity_container.entities_in_radius(
+                    position.clone(),
+                    payload.splash_radius,
+                    filter,
+                ) {
+                    let distance = (entity_hit.borrow().get_position() - position.clone()).length();
+                    damage_entity(&entity_hit, payload, falloff_percent(distance));
+                }
+                for building_hit in self.building_container.get_buildings_in_radius(
+                    &position,
+                    payload.splash_radius,
+                    not_team,
+                ) {
+                    let distance = {
+                        let building = building_hit.borrow();
+                        distance_to_big_block(
+                            &position,
+                            &building.get_position(),
+                            &Vec2i::new(building.get_width(), building.get_height()),
+                        )
+                    };
+                    damage_building(&building_hit, payload, falloff_percent(distance));
+                }
+                continue;
+            }
+
+            if let Some(entity_hit) = self.entity_container.get_closest_entity(
+                position.clone(),
+                1.0, // TODO: Is this right??
+                EntityFilter::not_team(projectile.get_team()),
+            ) {
+                damage_entity(&entity_hit, payload, 100);
+            }
+            if let Some(building_hit) = self.building_container.get_building_at(
+                &position.as_vec2i(),
+                None,
+                Some(projectile.get_team()),
+            ) {
+                damage_building(&building_hit, payload, 100);
+            }
+        }
+        self.projectile_handler.remove_impacting_projectiles(); // Since impacting projectiles have been handled, remove them
+    }
+
     fn decrement_team_resources(&mut self, team_id: u8, amount: Resources) -> bool {
         for team in self.teams.iter_mut() {
             if team.get_id() == team_id {
@@ -794,19 +694,55 @@ impl Game {
                 return false;
             }
         }
-        return false;
+        false
     }
 
-    pub fn command_building_spawn(&mut self, building_id: usize, entity_type: EntityType) {
+    fn command_building_spawn(&mut self, building_id: Handle, entity_type: &EntityType) {
+        let definition = match self.entity_registry.get(entity_type) {
+            Some(definition) => definition.clone(),
+            None => {
+                println!("Unknown entity type {:?}", entity_type);
+                return;
+            }
+        };
         if let Some(building) = self.building_container.get_building_by_id(building_id) {
             let team = building.borrow().get_team();
 
-            if self.decrement_team_resources(team, Resources::new(80)) {
+            if !building.borrow().is_constructed() {
+                println!("Building is not constructed yet");
+                return;
+            }
+            if !building.borrow().get_definition().can_produce(entity_type) {
+                println!(
+                    "{:?} can not produce {:?}",
+                    building.borrow().get_building_type(),
+                    entity_type
+                );
+                return;
+            }
+
+            if building.borrow().get_spawn_queue().len() >= MAX_SPAWN_QUEUE_LENGTH {
+                println!("Spawn queue is full");
+                return;
+            }
+            let has_supply = self
+                .teams
+                .iter()
+                .any(|t| t.get_id() == team && t.has_supply_for(definition.supply));
+            if !has_supply {
+                println!("Not enough supply to spawn {:?}", entity_type);
+                return;
+            }
+
+            if self.decrement_team_resources(team, definition.cost.clone()) {
                 self.building_container
-                    .add_to_building_spawn_queue(building_id, entity_type);
+                    .add_to_building_spawn_queue(building_id, definition);
+                self.recount_supply();
+            } else {
+                println!("Not enough resources to spawn {:?}", entity_type);
             }
         } else {
-            println!("Building with id {} not found", building_id);
+            println!("Building with id {:?} not found", building_id);
         }
         // self.building_container.get_building_by_id(building_id).unwrap().add_to_spawn_queue(entity_type);
         // for building in self.building_container.get_buildings().iter() {
@@ -820,12 +756,157 @@ impl Game {
         // }
     }
 
-    pub fn command_entities_simple(
-        &mut self,
-        entity_ids: &Vec<usize>,
-        is_idle: bool,
-        is_hold: bool,
-    ) {
+    fn command_cancel_spawn(&mut self, building_id: Handle, index: Option<usize>) {
+        let building = match self.building_container.get_building_by_id(building_id) {
+            Some(building) => building,
+            None => {
+                println!("Building with id {:?} not found", building_id);
+                return;
+            }
+        };
+        let team = building.borrow().get_team();
+        let index = match index {
+            Some(index) => index,
+            None => match building.borrow().get_spawn_queue().len() {
+                0 => return,
+                len => len - 1,
+            },
+        };
+
+        let cancelled = building.borrow_mut().remove_from_spawn_queue(index);
+        if let Some(definition) = cancelled {
+            if let Some(team) = self.teams.iter_mut().find(|t| t.get_id() == team) {
+                team.increment_resources(&definition.cost);
+            }
+            self.recount_supply();
+        }
+    }
+
+    // Takes the building off the map, refunds the given share of its cost and the spawn queue and
+    // stops the workers constructing it
+    fn remove_building(&mut self, building_id: Handle, refund_percent: i32) {
+        let building = match self
+            .building_container
+            .remove_building(building_id, &mut self.ground)
+        {
+            Some(building) => building,
+            None => {
+                println!("Building with id {:?} not found", building_id);
+                return;
+            }
+        };
+        let building = building.borrow();
+
+        let mut refund = &(&building.get_definition().cost * refund_percent) / 100;
+        for definition in building.get_spawn_queue().iter() {
+            refund.increment(&definition.cost);
+        }
+        if let Some(team) = self
+            .teams
+            .iter_mut()
+            .find(|t| t.get_id() == building.get_team())
+        {
+            team.increment_resources(&refund);
+        }
+
+        for entity in self.entity_container.iter_alive() {
+            if entity.borrow().get_build_target() == Some(building_id) {
+                entity.borrow_mut().set_action_idle();
+            }
+        }
+
+        self.recount_supply();
+    }
+
+    fn command_cancel_construction(&mut self, building_id: Handle, team: u8) {
+        match self.building_container.get_building_by_id(building_id) {
+            Some(building) if building.borrow().get_team() != team => {
+                println!("Building with id {:?} belongs to another team", building_id);
+            }
+            Some(building) if building.borrow().is_constructed() => {
+                println!("Building is already constructed, demolish it instead");
+            }
+            Some(_) => self.remove_building(building_id, CANCEL_CONSTRUCTION_REFUND_PERCENT),
+            None => println!("Building with id {:?} not found", building_id),
+        }
+    }
+
+    fn command_demolish(&mut self, building_id: Handle, team: u8) {
+        match self.building_container.get_building_by_id(building_id) {
+            Some(building) if building.borrow().get_team() != team => {
+                println!("Building with id {:?} belongs to another team", building_id);
+            }
+            Some(building) if !building.borrow().is_constructed() => {
+                println!("Building is under construction, cancel it instead");
+            }
+            Some(_) => self.remove_building(building_id, 0),
+            None => println!("Building with id {:?} not found", building_id),
+        }
+    }
+
+    // What right clicking a position means: attack an enemy, gather from a resource, help
+    // constructing or repair an own building or just move there
+    pub fn command_at(
+        &self,
+        entity_ids: Vec<Handle>,
+        position: &Vec2f,
+        is_attack: bool,
+    ) -> Command {
+        let team = entity_ids
+            .first()
+            .and_then(|entity_id| self.entity_container.get_by_id(*entity_id))
+            .map(|entity| entity.borrow().get_team());
+        // Enemies in the fog of war can not be targeted
+        let enemy_entity = team.and_then(|team| {
+            self.entity_container.get_closest_entity_where(
+                position.clone(),
+                0.5,
+                EntityFilter::not_team(team),
+                |entity| self.is_entity_seen_by(entity, Some(team)),
+            )
+        });
+        let enemy_building = team.and_then(|team| {
+            self.building_container
+                .get_building_at(&position.as_vec2i(), None, Some(team))
+                .filter(|building| self.is_building_seen_by(&building.borrow(), Some(team)))
+        });
+        if let Some(enemy_entity) = enemy_entity {
+            Command::Attack {
+                entity_ids,
+                target: AttackTarget::Entity(enemy_entity.borrow().get_id()),
+            }
+        } else if let Some(enemy_building) = enemy_building {
+            Command::Attack {
+                entity_ids,
+                target: AttackTarget::Building(enemy_building.borrow().get_id()),
+            }
+        } else if is_attack {
+            Command::AttackMove {
+                entity_ids,
+                position: position.clone(),
+            }
+        } else if self.ground.get_pos(position).get_resource_type().is_some() {
+            Command::Gather {
+                entity_ids,
+                position: position.as_vec2i(),
+            }
+        } else if let Some(building) = team.and_then(|team| {
+            self.building_container
+                .get_building_at(&position.as_vec2i(), Some(team), None)
+        }) {
+            Command::Build {
+                entity_ids,
+                building_id: building.borrow().get_id(),
+            }
+        } else {
+            Command::Move {
+                entity_ids,
+                position: position.clone(),
+            }
+        }
+    }
+
+    fn command_entities_simple(&mut self, entity_ids: &[Handle], is_idle: bool, is_hold: bool) {
         let entities_commanded: Vec<&Rc<RefCell<Entity>>> = self
             .entity_container
             .iter_alive()
@@ -842,16 +923,86 @@ impl Game {
         }
     }
 
-    pub fn set_spawn_command_position(&mut self, building_id: usize, pos: &Vec2f) {
+    fn set_spawn_command_position(&mut self, building_id: Handle, pos: &Vec2f) {
         self.building_container
             .set_spawn_command_position(building_id, pos);
     }
 
-    pub fn command_construct_building(
+    // Used both when constructing and to colour the placement preview
+    pub fn check_building_placement(
+        &self,
+        top_left: &Vec2i,
+        building_type: BuildingType,
+    ) -> Result<(), PlacementError> {
+        let definition = self
+            .building_registry
+            .get(building_type)
+            .ok_or(PlacementError::UnknownBuildingType)?;
+
+        // The outermost tiles are always walls
+        if top_left.x < 1
+            || top_left.y < 1
+            || top_left.x + definition.width > self.ground.get_width() - 1
+            || top_left.y + definition.height > self.ground.get_height() - 1
+        {
+            return Err(PlacementError::OutOfBounds);
+        }
+
+        for x in 0..definition.width {
+            for y in 0..definition.height {
+                if self.ground.blocked_at(top_left.x + x, top_left.y + y) {
+                    return Err(PlacementError::Blocked);
+                }
+            }
+        }
+
+        // Keep `spacing` free tiles between this and other buildings so that units can walk around
+        let spacing = definition.spacing;
+        for building in self.building_container.get_buildings().iter() {
+            let building = building.borrow();
+            let position = building.get_position();
+            if top_left.x - spacing < position.x + building.get_width()
+                && position.x < top_left.x + definition.width + spacing
+                && top_left.y - spacing < position.y + building.get_height()
+                && position.y < top_left.y + definition.height + spacing
+            {
+                return Err(PlacementError::TooClose);
+            }
+        }
+
+        Ok(())
+    }
+
+    // Units standing where a building was just placed would be trapped inside it
+    fn push_entities_out_of(&mut self, top_left: &Vec2i, size: &Vec2i) {
+        for entity in self.entity_container.iter_alive() {
+            let position = entity.borrow().get_position();
+            let is_inside = position.x >= top_left.x as f32
+                && position.x < (top_left.x + size.x) as f32
+                && position.y >= top_left.y as f32
+                && position.y < (top_left.y + size.y) as f32;
+            if is_inside {
+                if let Some(free_position) = self.ground.nearest_unblocked(&position) {
+                    entity.borrow_mut().set_position(free_position);
+                }
+            }
+        }
+    }
+
+    fn command_construct_building(
         &mut self,
-        entity_ids: &Vec<usize>,
+        entity_ids: &[Handle],
         building_top_left: &Vec2i,
+        building_type: BuildingType,
     ) {
+        let definition = match self.building_registry.get(building_type) {
+            Some(definition) => definition.clone(),
+            None => {
+                println!("Unknown building type {:?}", building_type);
+                return;
+            }
+        };
+
         let mut team_id: Option<u8> = None;
 
         let mut entities = Vec::new();
@@ -875,31 +1026,28 @@ impl Game {
         }
 
         if let Some(team_id) = team_id {
-            for x in 0..2 {
-                for y in 0..2 {
-                    if self
-                        .ground
-                        .blocked_at(building_top_left.x + x, building_top_left.y + y)
-                    {
-                        println!("Cannot construct building on non-empty tile");
-                        return;
-                    }
-                }
+            if let Err(placement_error) =
+                self.check_building_placement(building_top_left, building_type)
+            {
+                println!("Cannot construct building here: {:?}", placement_error);
+                return;
             }
 
-            if self.decrement_team_resources(team_id, Resources::new(100)) {
-                let new_building_width = 2;
-                let new_building_height = 2;
-
-                let new_building_ref = self.building_container.add_building(
-                    Building::new(
-                        building_top_left.clone(),
-                        new_building_width,
-                        new_building_height,
-                        team_id,
-                        false,
-                    ),
-                    &mut self.ground,
+            if self.decrement_team_resources(team_id, definition.cost.clone()) {
+                let new_building_width = definition.width;
+                let new_building_height = definition.height;
+
+                let new_building_id = self
+                    .building_container
+                    .add_building(
+                        Building::new(building_top_left.clone(), team_id, definition, false),
+                        &mut self.ground,
+                    )
+                    .borrow()
+                    .get_id();
+                self.push_entities_out_of(
+                    building_top_left,
+                    &Vec2i::new(new_building_width, new_building_height),
                 );
 
                 let new_path = self.path_finder.find_path(
@@ -918,7 +1066,7 @@ impl Game {
                 for entity in entities.iter() {
                     entity
                         .borrow_mut()
-                        .set_action_build(new_building_ref.clone(), new_path.clone());
+                        .set_action_build(new_building_id, new_path.clone());
                     // entity.borrow_mut().set_action_construct_building(new_building_ref.clone());
                 }
             } else {
@@ -929,40 +1077,13 @@ impl Game {
         }
     }
 
-    pub fn command_entities_move(
+    fn command_entities_move(
         &mut self,
-        entity_ids: &Vec<usize>,
+        entity_ids: &[Handle],
         goal_pos: &Vec2f,
-        is_attack_command: bool,
+        move_goal_type: MoveGoalType,
     ) {
-        enum MoveGoalType {
-            Move,
-            Attack,
-            Gather,
-            Build(Rc<RefCell<Building>>),
-        }
-
         // TODO: Make sure only one team entities are selected
-        // TODO: Get the team id and filter buildings by it
-
-        let move_goal_type: MoveGoalType = if is_attack_command {
-            MoveGoalType::Attack
-        } else if self.ground.get_pos(goal_pos) == GroundType::Gold {
-            MoveGoalType::Gather
-        } else if let Some(building) = self.building_container.get_building_at(
-            &goal_pos.as_vec2i(),
-            None, // TODO: Filter here by team id
-            None,
-        ) {
-            MoveGoalType::Build(building)
-        } else {
-            MoveGoalType::Move
-        };
-
-        // let is_gather_command = match self.ground.get_pos(goal_pos) {
-        //     GroundType::Gold => true,
-        //     _ => false,
-        // };
 
         let entity_positions_iter = self
             .entity_container
@@ -975,7 +1096,7 @@ impl Game {
             MoveGoalType::Move | MoveGoalType::Attack => PathGoal::Point {
                 pos: goal_pos.clone(),
             },
-            MoveGoalType::Gather => PathGoal::Rect {
+            MoveGoalType::Gather(_) => PathGoal::Rect {
                 pos: goal_pos.as_vec2i(),
                 size: Vec2i::new(1, 1),
             },
@@ -986,6 +1107,7 @@ impl Game {
                     building.borrow().get_height(),
                 ),
             },
+            MoveGoalType::AttackTarget(_, path_goal) => path_goal.clone(),
         };
 
         let found_path = self
@@ -1010,61 +1132,236 @@ impl Game {
                 .sum::<f32>();
 
             for entity in entities_commanded {
-                let entity_type = entity.borrow().get_entity_type();
+                let is_worker = entity.borrow().is_worker();
 
                 match &move_goal_type {
                     MoveGoalType::Move => {
                         entity.borrow_mut().set_action_move(
                             found_path.clone(),
-                            &goal_pos,
+                            goal_pos,
                             entity_mass,
                         );
                     }
                     MoveGoalType::Attack => {
                         entity.borrow_mut().set_action_attack(
                             found_path.clone(),
-                            &goal_pos,
+                            goal_pos,
                             entity_mass,
                         );
                     }
-                    MoveGoalType::Gather => match entity_type {
-                        EntityType::Worker => {
+                    MoveGoalType::Gather(resource_type) => {
+                        if is_worker {
                             entity.borrow_mut().set_action_gather(
                                 goal_pos.as_vec2i(),
-                                0,
+                                *resource_type,
                                 Some(found_path.clone()),
                             );
                         }
-                        _ => {}
-                    },
+                    }
+                    MoveGoalType::AttackTarget(target, _) => {
+                        if entity.borrow().can_attack() {
+                            entity
+                                .borrow_mut()
+                                .set_action_attack_target(*target, Some(found_path.clone()));
+                        } else {
+                            entity.borrow_mut().set_action_move(
+                                found_path.clone(),
+                                goal_pos,
+                                entity_mass,
+                            );
+                        }
+                    }
                     MoveGoalType::Build(building) => {
-                        entity
-                            .borrow_mut()
-                            .set_action_build(building.clone(), Some(found_path.clone()));
+                        if is_worker {
+                            entity.borrow_mut().set_action_build(
+                                building.borrow().get_id(),
+                                Some(found_path.clone()),
+                            );
+                        } else {
+                            entity.borrow_mut().set_action_move(
+                                found_path.clone(),
+                                goal_pos,
+                                entity_mass,
+                            );
+                        }
                     }
                 };
             }
         }
     }
 
+    fn command_attack(&mut self, entity_ids: &[Handle], target: AttackTarget) {
+        let (position, path_goal) = match target {
+            AttackTarget::Entity(entity_id) => match self.entity_container.get_by_id(entity_id) {
+                Some(entity) => {
+                    let position = entity.borrow().get_position();
+                    let path_goal = PathGoal::Rect {
+                        pos: position.as_vec2i(),
+                        size: Vec2i::new(1, 1),
+                    };
+                    (position, path_goal)
+                }
+                None => {
+                    println!("Entity with id {:?} not found", entity_id);
+                    return;
+                }
+            },
+            AttackTarget::Building(building_id) => {
+                match self.building_container.get_building_by_id(building_id) {
+                    Some(building) => {
+                        let building = building.borrow();
+                        let path_goal = PathGoal::Rect {
+                            pos: building.get_position(),
+                            size: Vec2i::new(building.get_width(), building.get_height()),
+                        };
+                        (building.get_center_position(), path_goal)
+                    }
+                    None => {
+                        println!("Building with id {:?} not found", building_id);
+                        return;
+                    }
+                }
+            }
+        };
+        self.command_entities_move(
+            entity_ids,
+            &position,
+            MoveGoalType::AttackTarget(target, path_goal),
+        );
+    }
+
+    pub fn queue_command(&mut self, command: Command) {
+        self.command_queue.push(command);
+    }
+
+    fn execute_command(&mut self, command: &Command) {
+        match command {
+            Command::Move {
+                entity_ids,
+                position,
+            } => self.command_entities_move(entity_ids, position, MoveGoalType::Move),
+            Command::AttackMove {
+                entity_ids,
+                position,
+            } => self.command_entities_move(entity_ids, position, MoveGoalType::Attack),
+            Command::Attack { entity_ids, target } => self.command_attack(entity_ids, *target),
+            Command::Gather {
+                entity_ids,
+                position,
+            } => match self
+                .ground
+                .get_at(position.x, position.y)
+                .get_resource_type()
+            {
+                Some(resource_type) => self.command_entities_move(
+                    entity_ids,
+                    &position.as_vec2f(),
+                    MoveGoalType::Gather(resource_type),
+                ),
+                None => println!("Nothing to gather at {:?}", position),
+            },
+            Command::Build {
+                entity_ids,
+                building_id,
+            } => {
+                if let Some(building) = self.building_container.get_building_by_id(*building_id) {
+                    let position = building.borrow().get_center_position();
+                    self.command_entities_move(
+                        entity_ids,
+                        &position,
+                        MoveGoalType::Build(building),
+                    );
+                } else {
+                    println!("Building with id {:?} not found", building_id);
+                }
+            }
+            Command::ConstructBuilding {
+                entity_ids,
+                top_left,
+                building_type,
+            } => self.command_construct_building(entity_ids, top_left, *building_type),
+            Command::CancelConstruction { building_id, team } => {
+                self.command_cancel_construction(*building_id, *team)
+            }
+            Command::Demolish { building_id, team } => self.command_demolish(*building_id, *team),
+            Command::Spawn {
+                building_id,
+                entity_type,
+            } => self.command_building_spawn(*building_id, entity_type),
+            Command::CancelSpawn { building_id, index } => {
+                self.command_cancel_spawn(*building_id, *index)
+            }
+            Command::Rally {
+                building_id,
+                position,
+            } => self.set_spawn_command_position(*building_id, position),
+            Command::Hold { entity_ids } => self.command_entities_simple(entity_ids, false, true),
+            Command::Idle { entity_ids } => self.command_entities_simple(entity_ids, true, false),
+            Command::SetGround {
+                position,
+                ground_type,
+            } => self
+                .ground
+                .set_at(position.x, position.y, ground_type.clone()),
+        }
+    }
+
     pub fn update(&mut self) {
+        // Commands are recorded with the tick they were applied on so that the game can be replayed
+        for command in std::mem::take(&mut self.command_queue) {
+            self.execute_command(&command);
+            self.command_log.push(RecordedCommand {
+                tick: self.tick,
+                command,
+            });
+        }
+        self.tick += 1;
+
         // Make sure entity container is up to date
         self.entity_container.update_entities_by_area();
+        self.update_visibility();
+        self.stop_attacks_into_fog();
 
-        let mut entity_close: Vec<(
-            &Rc<RefCell<Entity>>,
-            Option<Rc<RefCell<Entity>>>,
-            Vec<Rc<RefCell<Entity>>>,
-            Option<Rc<RefCell<Building>>>,
-        )> = Vec::new();
+        let mut entity_close: Vec<EntitySurroundings> = Vec::new();
         for entity1 in self.entity_container.iter_alive() {
             let entity1_position = entity1.borrow().get_position();
 
-            let closest_enemy = self.entity_container.get_closest_entity(
-                entity1.borrow().get_position().clone(),
-                8.0,
-                EntityFilter::not_team(entity1.borrow().get_team()),
-            );
+            // An entity attacking a specific unit only cares about that one
+            let attack_target = entity1.borrow().get_attack_target();
+            // Enemies in the fog of war are ignored, and enemies that can be seen directly are
+            // preferred over closer ones behind walls
+            let team = entity1.borrow().get_team();
+            let visibility = self.get_team_visibility(team);
+            let is_seen = |entity: &Entity| {
+                visibility.is_some_and(|visibility| visibility.is_visible(&entity.get_position()))
+            };
+            let closest_enemy = match attack_target {
+                Some(AttackTarget::Entity(target_id)) => {
+                    self.entity_container.get_by_id(target_id).cloned()
+                }
+                Some(AttackTarget::Building(_)) => None,
+                None => self
+                    .entity_container
+                    .get_closest_entity_where(
+                        entity1_position.clone(),
+                        8.0,
+                        EntityFilter::not_team(team),
+                        |entity| {
+                            is_seen(entity)
+                                && self
+                                    .ground
+                                    .has_line_of_sight(&entity1_position, &entity.get_position())
+                        },
+                    )
+                    .or_else(|| {
+                        self.entity_container.get_closest_entity_where(
+                            entity1_position.clone(),
+                            8.0,
+                            EntityFilter::not_team(team),
+                            is_seen,
+                        )
+                    }),
+            };
 
             let mut close_entities: Vec<Rc<RefCell<Entity>>> = Vec::new();
             for entity in self
@@ -1081,8 +1378,11 @@ impl Game {
             let closest_enemy_building = self.building_container.get_closest_building(
                 &entity1.borrow().get_position().as_vec2i(),
                 None,
-                Some(entity1.borrow().get_team()),
+                Some(team),
                 8.0,
+                |building| {
+                    visibility.is_some_and(|visibility| is_building_visible(visibility, building))
+                },
             );
 
             entity_close.push((
@@ -1106,6 +1406,8 @@ impl Game {
                 entity1.borrow_mut().update(
                     closest_enemy.clone(),
                     closest_enemy_building.clone(),
+                    &self.building_container,
+                    &self.ground,
                     // &mut self.projectile_handler,
                     step_n,
                     step_delta,
@@ -1158,28 +1460,136 @@ impl Game {
         }
 
         // Update buildings
-        self.building_container.update_buildings(&mut event_handler);
+        self.building_container.update_buildings(
+            &self.entity_container,
+            &self.ground,
+            &self.teams,
+            &mut event_handler,
+        );
 
         while let Some(event) = event_handler.events.pop() {
             match event {
-                Event::AddRangedProjectile { start, end, team } => self
+                Event::AddRangedProjectile {
+                    start,
+                    end,
+                    kind,
+                    payload,
+                    team,
+                    homing_target,
+                } => self.projectile_handler.add_ranged_projectile(
+                    start,
+                    end,
+                    kind,
+                    payload,
+                    team,
+                    homing_target,
+                ),
+                Event::AddMeleeProjectile { end, payload, team } => self
                     .projectile_handler
-                    .add_ranged_projectile(start, end, team),
-                Event::AddMeleeProjectile { end, team } => {
-                    self.projectile_handler.add_meelee_projectile(end, team)
+                    .add_meelee_projectile(end, payload, team),
+                Event::TakeResource {
+                    entity_id,
+                    resource_position,
+                } => {
+                    let entity_ref = match self.entity_container.get_by_id(entity_id) {
+                        Some(entity_ref) => entity_ref.clone(),
+                        None => continue, // The entity died after making the request
+                    };
+                    let taken = self.ground.take_resource(
+                        resource_position.x,
+                        resource_position.y,
+                        GATHER_AMOUNT,
+                    );
+                    if taken > 0 {
+                        entity_ref.borrow_mut().set_gather_carrying(taken);
+                        event_handler.add_event(Event::RequestGatherPath {
+                            entity_id,
+                            going_towards_resource: false,
+                            resource_position: resource_position.clone(),
+                        });
+                    }
+                    if self
+                        .ground
+                        .get_at(resource_position.x, resource_position.y)
+                        .get_resource_type()
+                        .is_none()
+                    {
+                        // The deposit is depleted. The paths leading to it are useless now, so
+                        // everyone heading there looks for another deposit.
+                        for other in self.entity_container.iter_alive() {
+                            if other.borrow().is_going_to_gather_at(&resource_position) {
+                                event_handler.add_event(Event::RequestGatherPath {
+                                    entity_id: other.borrow().get_id(),
+                                    going_towards_resource: true,
+                                    resource_position: resource_position.clone(),
+                                });
+                            }
+                        }
+                    }
+                }
+                Event::RepairBuilding {
+                    entity_id,
+                    building_id,
+                } => {
+                    let building = match self.building_container.get_building_by_id(building_id) {
+                        Some(building) => building,
+                        None => continue, // Destroyed, the worker notices this itself
+                    };
+                    let team_id = building.borrow().get_team();
+                    let cost = building.borrow().get_repair_step_cost();
+                    if self.decrement_team_resources(team_id, cost.clone()) {
+                        building.borrow_mut().repair(&cost);
+                    } else {
+                        println!("Not enough resources to repair the building");
+                        if let Some(entity) = self.entity_container.get_by_id(entity_id) {
+                            entity.borrow_mut().set_action_idle();
+                        }
+                    }
                 }
                 Event::RequestGatherPath {
                     entity_id,
                     going_towards_resource,
-                    resource_position,
+                    mut resource_position,
                 } => {
-                    let entity_ref = self.entity_container.get_by_id(entity_id);
+                    let entity_ref = match self.entity_container.get_by_id(entity_id) {
+                        Some(entity_ref) => entity_ref.clone(),
+                        None => continue, // The entity died after making the request
+                    };
                     let entity_position: Vec2i = entity_ref.borrow().get_position().as_vec2i();
                     let positions: HashSet<Vec2i> =
                         [entity_position.clone()].iter().cloned().collect();
                     let entity_team = entity_ref.borrow().get_team();
 
                     if going_towards_resource {
+                        let resource_type = match entity_ref.borrow().get_gather_resource_type() {
+                            Some(resource_type) => resource_type,
+                            None => continue, // Not gathering anymore
+                        };
+                        if self
+                            .ground
+                            .get_at(resource_position.x, resource_position.y)
+                            .get_resource_type()
+                            != Some(resource_type)
+                        {
+                            // Depleted, continue with the closest deposit of the same type
+                            match self
+                                .ground
+                                .find_closest_resource(&resource_position, resource_type)
+                            {
+                                Some(new_position) => {
+                                    resource_position = new_position;
+                                    entity_ref
+                                        .borrow_mut()
+                                        .set_gather_resource_position(resource_position.clone());
+                                }
+                                None => {
+                                    println!("No {:?} left to gather", resource_type);
+                                    entity_ref.borrow_mut().set_action_idle();
+                                    continue;
+                                }
+                            }
+                        }
+
                         let path = self.path_finder.find_path(
                             &self.ground,
                             PathGoal::Rect {
@@ -1192,14 +1602,9 @@ impl Game {
                         self.debug_path = path.clone();
                     } else {
                         // TODO: Can not get the closest building like this. Must use path finding instead
-                        if let Some(closest_building) =
-                            self.building_container.get_closest_building(
-                                // let closest_building: &Building = self.building_container.get_closest_building(
-                                &entity_position,
-                                Some(entity_team),
-                                None,
-                                9999999.9,
-                            )
+                        if let Some(closest_building) = self
+                            .building_container
+                            .get_closest_drop_off_building(&entity_position, entity_team)
                         {
                             let path = self.path_finder.find_path(
                                 &self.ground,
@@ -1222,88 +1627,210 @@ impl Game {
                                 //     ),
                                 // )),
                             );
+                            entity_ref
+                                .borrow_mut()
+                                .set_drop_off_building(Some(closest_building.borrow().get_id()));
                             self.debug_path = path.clone();
                         } else {
                             println!("No path to the building (no building found)");
+                            entity_ref.borrow_mut().set_action_idle();
                         }
                     }
                 }
                 Event::IncrementResources { team, amounts } => {
-                    let team = self
-                        .teams
-                        .iter_mut()
-                        .filter(|t| t.get_id() == team)
-                        .next()
-                        .unwrap();
+                    let team = self.teams.iter_mut().find(|t| t.get_id() == team).unwrap();
                     team.increment_resources(&amounts);
                 }
                 Event::SpawnEntity {
-                    entity_type,
+                    definition,
                     building_id,
                     team,
                 } => {
                     if let Some(building) = self.building_container.get_building_by_id(building_id)
                     {
                         println!("Spwaning an entity");
-                        let new_entity = Entity::new_params(
+                        let new_entity = Entity::new(
                             building.borrow_mut().get_spawn_position(),
                             team,
-                            entity_type,
+                            definition,
                         );
-                        let new_entity_id = new_entity.get_id();
-                        self.entity_container.spawn_entity(new_entity);
+                        let new_entity_id = self.entity_container.spawn_entity(new_entity);
                         if let Some(building_command_pos) =
                             building.borrow().get_spawn_command_position()
                         {
                             println!("Spawning entity: Commanding to move to position");
-                            self.command_entities_move(
-                                &vec![new_entity_id],
-                                &building_command_pos,
-                                false,
-                            );
+                            let command =
+                                self.command_at(vec![new_entity_id], &building_command_pos, false);
+                            self.execute_command(&command);
                         };
                     } else {
                         println!("Building that does not exists is spawning an entity");
                     }
                 }
                 Event::RequestRePath { entity_id } => {
-                    let entity_ref = self.entity_container.get_by_id(entity_id);
+                    let entity_ref = match self.entity_container.get_by_id(entity_id) {
+                        Some(entity_ref) => entity_ref,
+                        None => continue, // The entity died after making the request
+                    };
                     let mut entity = entity_ref.borrow_mut();
 
                     println!("Rep pathing request handler");
                     entity.refresh_path(&mut self.path_finder, &self.ground);
                 }
+                Event::RequestAttackPath {
+                    entity_id,
+                    path_goal,
+                } => {
+                    let entity_ref = match self.entity_container.get_by_id(entity_id) {
+                        Some(entity_ref) => entity_ref,
+                        None => continue, // The entity died after making the request
+                    };
+                    let positions: HashSet<Vec2i> = [entity_ref.borrow().get_position().as_vec2i()]
+                        .iter()
+                        .cloned()
+                        .collect();
+                    if let Some(path) =
+                        self.path_finder
+                            .find_path(&self.ground, path_goal, &positions)
+                    {
+                        entity_ref.borrow_mut().update_path(Some(path));
+                    }
+                }
             }
         }
 
-        // Update projectiles
-        self.projectile_handler.progress_projectiles();
-        for projectile in self.projectile_handler.get_impacting_projectiles() {
-            if let Some(entity_hit) = self.entity_container.get_closest_entity(
-                projectile.get_position().clone(),
-                1.0, // TODO: Is this right??
-                EntityFilter::not_team(projectile.get_team()),
-            ) {
-                entity_hit
-                    .borrow_mut()
-                    .health
-                    .take_damage(projectile.get_damage());
-            }
-            if let Some(building_hit) = self.building_container.get_building_at(
-                &projectile.get_position().as_vec2i(),
-                None,
-                Some(projectile.get_team()),
-            ) {
-                building_hit
-                    .borrow_mut()
-                    .health
-                    .take_damage(projectile.get_damage());
-            }
-        }
-        self.projectile_handler.remove_impacting_projectiles(); // Since impacting projectiles have been handled, remove them
+        self.update_projectiles();
 
         // Remove dead
         self.entity_container.remove_dead();
         self.building_container.remove_dead(&mut self.ground);
+
+        self.recount_supply();
+    }
+}
+
+// `percent` of the payload damage is dealt, before armor
+fn damage_entity(entity: &Rc<RefCell<Entity>>, payload: &ProjectilePayload, percent: i32) {
+    let mut entity = entity.borrow_mut();
+    let damage = calculate_damage(
+        payload.damage * percent / 100,
+        payload.damage_type,
+        entity.get_definition().armor,
+        entity.get_definition().armor_type,
+    );
+    entity.health.take_damage(damage);
+}
+
+fn damage_building(building: &Rc<RefCell<Building>>, payload: &ProjectilePayload, percent: i32) {
+    let mut building = building.borrow_mut();
+    let damage = calculate_damage(
+        payload.damage * percent / 100,
+        payload.damage_type,
+        building.get_definition().armor,
+        building.get_definition().armor_type,
+    );
+    building.health.take_damage(damage);
+}
+
+fn is_building_visible(visibility: &VisibilityGrid, building: &Building) -> bool {
+    visibility.is_area_visible(
+        &building.get_position(),
+        &Vec2i::new(building.get_width(), building.get_height()),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn new_game() -> Game {
+        Game::new(1, EntityRegistry::default(), BuildingRegistry::default())
+    }
+
+    fn clear_ground(game: &mut Game, top_left: &Vec2i, size: &Vec2i) {
+        for x in top_left.x..top_left.x + size.x {
+            for y in top_left.y..top_left.y + size.y {
+                game.ground.set_at(x, y, GroundType::Empty);
+            }
+        }
+    }
+
+    #[test]
+    fn placement_outside_the_inner_ground_is_out_of_bounds() {
+        let game = new_game();
+        for top_left in [
+            Vec2i::new(0, 20),
+            Vec2i::new(20, 0),
+            Vec2i::new(GROUND_WIDTH - 3, 20),
+            Vec2i::new(20, GROUND_HEIGHT - 2),
+        ] {
+            assert_eq!(
+                game.check_building_placement(&top_left, BuildingType::Barracks),
+                Err(PlacementError::OutOfBounds)
+            );
+        }
+    }
+
+    #[test]
+    fn placement_on_a_wall_is_blocked() {
+        let mut game = new_game();
+        let top_left = Vec2i::new(30, 30);
+        clear_ground(&mut game, &top_left, &Vec2i::new(3, 2));
+        assert_eq!(
+            game.check_building_placement(&top_left, BuildingType::Barracks),
+            Ok(())
+        );
+
+        game.ground.set_at(32, 31, GroundType::Wall);
+        assert_eq!(
+            game.check_building_placement(&top_left, BuildingType::Barracks),
+            Err(PlacementError::Blocked)
+        );
+    }
+
+    #[test]
+    fn placement_next_to_a_building_is_too_close() {
+        // The town hall of team 0 covers 8..11 on both axes and barracks keep one free tile
+        let mut game = new_game();
+        clear_ground(&mut game, &Vec2i::new(11, 8), &Vec2i::new(4, 2));
+        assert_eq!(
+            game.check_building_placement(&Vec2i::new(11, 8), BuildingType::Barracks),
+            Err(PlacementError::TooClose)
+        );
+        assert_eq!(
+            game.check_building_placement(&Vec2i::new(12, 8), BuildingType::Barracks),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn only_the_owning_team_can_demolish_a_building() {
+        let mut game = new_game();
+        let building_id = game
+            .building_container
+            .get_building_at(&Vec2i::new(8, 8), Some(0), None)
+            .unwrap()
+            .borrow()
+            .get_id();
+
+        game.queue_command(Command::Demolish {
+            building_id,
+            team: 1,
+        });
+        game.update();
+        assert!(game
+            .building_container
+            .get_building_by_id(building_id)
+            .is_some());
+
+        game.queue_command(Command::Demolish {
+            building_id,
+            team: 0,
+        });
+        game.update();
+        assert!(game
+            .building_container
+            .get_building_by_id(building_id)
+            .is_none());
     }
 }
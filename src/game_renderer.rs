@@ -0,0 +1,1118 @@
+use crate::camera::Camera;
+use crate::draw::draw_health_bar;
+use raqote::{DrawOptions, DrawTarget, PathBuilder, Point, SolidSource, Source};
+use rts2::building_registry::BuildingType;
+use rts2::constants::{GROUND_HEIGHT, GROUND_WIDTH, SCREEN_HEIGHT, SCREEN_WIDTH, TILE_HW_RATIO};
+use rts2::entity::EntityFilter;
+use rts2::entity_registry::{EntityShape, ProjectileKind};
+use rts2::game::Game;
+use rts2::ground::GroundType;
+use rts2::handle::Handle;
+use rts2::vec::{Vec2f, Vec2i};
+use rts2::visibility::{Visibility, VisibilityGrid};
+
+const SPAWN_QUEUE_X: f32 = 180.;
+const SPAWN_QUEUE_ITEM_WIDTH: f32 = 100.;
+const SPAWN_QUEUE_ITEM_HEIGHT: f32 = 20.;
+
+const MINIMAP_LEFT: f32 = 10.;
+const MINIMAP_TOP: f32 = SCREEN_HEIGHT as f32 - 170.;
+const MINIMAP_SIZE: f32 = 160.;
+
+fn spawn_queue_item_baseline(index: usize) -> f32 {
+    SCREEN_HEIGHT as f32 - 170. + 40. + SPAWN_QUEUE_ITEM_HEIGHT * index as f32
+}
+
+// Which item of the selected building's spawn queue is under the cursor (screen coordinates)
+pub fn spawn_queue_index_at(screen_pos: &Vec2f, queue_length: usize) -> Option<usize> {
+    if screen_pos.x < SPAWN_QUEUE_X || screen_pos.x > SPAWN_QUEUE_X + SPAWN_QUEUE_ITEM_WIDTH {
+        return None;
+    }
+    (0..queue_length).find(|index| {
+        let baseline = spawn_queue_item_baseline(*index);
+        screen_pos.y > baseline - SPAWN_QUEUE_ITEM_HEIGHT && screen_pos.y <= baseline
+    })
+}
+
+// Pixels per tile on the minimap, squashed vertically like the main view
+fn minimap_tile_size() -> Vec2f {
+    let tile_width = (MINIMAP_SIZE / GROUND_WIDTH as f32)
+        .min(MINIMAP_SIZE / (GROUND_HEIGHT as f32 * TILE_HW_RATIO));
+    Vec2f::new(tile_width, tile_width * TILE_HW_RATIO)
+}
+
+// The map is centered in the minimap's square
+fn minimap_top_left() -> Vec2f {
+    let tile_size = minimap_tile_size();
+    Vec2f::new(
+        MINIMAP_LEFT + (MINIMAP_SIZE - tile_size.x * GROUND_WIDTH as f32) / 2.0,
+        MINIMAP_TOP + (MINIMAP_SIZE - tile_size.y * GROUND_HEIGHT as f32) / 2.0,
+    )
+}
+
+fn world_to_minimap(position: &Vec2f) -> Vec2f {
+    let top_left = minimap_top_left();
+    let tile_size = minimap_tile_size();
+    Vec2f::new(
+        top_left.x + position.x * tile_size.x,
+        top_left.y + position.y * tile_size.y,
+    )
+}
+
+// World position of the minimap under the cursor (screen coordinates)
+pub fn minimap_to_world(screen_pos: &Vec2f) -> Option<Vec2f> {
+    let top_left = minimap_top_left();
+    let tile_size = minimap_tile_size();
+    let position = Vec2f::new(
+        (screen_pos.x - top_left.x) / tile_size.x,
+        (screen_pos.y - top_left.y) / tile_size.y,
+    );
+    if position.x < 0.0
+        || position.x >= GROUND_WIDTH as f32
+        || position.y < 0.0
+        || position.y >= GROUND_HEIGHT as f32
+    {
+        return None;
+    }
+    Some(position)
+}
+
+fn draw_projectiles(game: &Game, dt: &mut DrawTarget, camera: &Camera, fog_team: Option<u8>) {
+    let mut path_builder = PathBuilder::new();
+    let visibility = fog_team.and_then(|team_id| game.get_team_visibility(team_id));
+
+    for projectile in game.get_projectile_handler().iter() {
+        let projectile_position = projectile.get_position();
+        if visibility.is_some_and(|visibility| !visibility.is_visible(&projectile_position)) {
+            continue;
+        }
+
+        let draw_pos =
+            camera.world_to_screen(&Vec2f::new(projectile_position.x, projectile_position.y));
+
+        let radius = match projectile.get_kind() {
+            ProjectileKind::Boulder => 0.2,
+            _ => 0.1,
+        };
+
+        path_builder.move_to(draw_pos.x, draw_pos.y);
+        path_builder.arc(
+            draw_pos.x,
+            draw_pos.y,
+            camera.length_to_pixels(radius),
+            0.0,
+            2.0 * std::f32::consts::PI,
+        );
+    }
+
+    dt.fill(
+        &path_builder.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(
+            255, 0x00, 0x00, 0x00,
+        )),
+        &DrawOptions::new(),
+    );
+}
+
+fn draw_buildings(
+    game: &Game,
+    dt: &mut DrawTarget,
+    camera: &Camera,
+    selected_building_id: &Option<Handle>,
+    fog_team: Option<u8>,
+) {
+    if let Some(visibility) = fog_team.and_then(|team_id| game.get_team_visibility(team_id)) {
+        draw_remembered_buildings(dt, camera, visibility);
+    }
+
+    for building_ref in game.get_building_container().get_buildings().iter() {
+        let building = building_ref.borrow();
+        if !game.is_building_seen_by(&building, fog_team) {
+            continue;
+        }
+
+        let mut path_builder = PathBuilder::new();
+        let mut selection_path_builder = PathBuilder::new();
+
+        let position = building.get_position();
+        let width = building.get_width();
+        let height = building.get_height();
+
+        let draw_pos = camera.world_to_screen(&Vec2f::new(
+            position.x as f32 + 0.1,
+            position.y as f32 + 0.1,
+        ));
+        path_builder.move_to(draw_pos.x, draw_pos.y);
+        path_builder.line_to(
+            draw_pos.x + camera.length_to_pixels_x(width as f32 - 0.2),
+            draw_pos.y,
+        );
+        path_builder.line_to(
+            draw_pos.x + camera.length_to_pixels_x(width as f32 - 0.2),
+            draw_pos.y + camera.length_to_pixels_y(height as f32 - 0.2),
+        );
+        path_builder.line_to(
+            draw_pos.x,
+            draw_pos.y + camera.length_to_pixels_y(height as f32 - 0.2),
+        );
+        path_builder.close();
+
+        let source = if building.get_team() == 0 {
+            Source::Solid(SolidSource::from_unpremultiplied_argb(
+                255, 0x7d, 0xde, 0x92,
+            ))
+        } else {
+            Source::Solid(SolidSource::from_unpremultiplied_argb(
+                255, 0xde, 0x7d, 0x92,
+            ))
+        };
+
+        if building.is_constructed() {
+            dt.fill(&path_builder.finish(), &source, &DrawOptions::new());
+        } else {
+            dt.stroke(
+                &path_builder.finish(),
+                &source,
+                &raqote::StrokeStyle::default(),
+                // stroke_style,
+                &DrawOptions::new(),
+                //     &selection_path,
+                // &selection_source,
+                // stroke_style,
+                // &DrawOptions::new(),
+            );
+        }
+
+        if let Some(selected_building_id) = selected_building_id {
+            if *selected_building_id == building.get_id() {
+                let selection_draw_pos =
+                    camera.world_to_screen(&Vec2f::new(position.x as f32, position.y as f32));
+                selection_path_builder.move_to(selection_draw_pos.x, selection_draw_pos.y);
+                selection_path_builder.line_to(
+                    selection_draw_pos.x + camera.length_to_pixels_x(width as f32),
+                    selection_draw_pos.y,
+                );
+                selection_path_builder.line_to(
+                    selection_draw_pos.x + camera.length_to_pixels_x(width as f32),
+                    selection_draw_pos.y + camera.length_to_pixels_y(height as f32),
+                );
+                selection_path_builder.line_to(
+                    selection_draw_pos.x,
+                    selection_draw_pos.y + camera.length_to_pixels_y(height as f32),
+                );
+                selection_path_builder.close();
+
+                let selection_path = selection_path_builder.finish();
+                let selection_source =
+                    Source::Solid(SolidSource::from_unpremultiplied_argb(255, 0, 255, 0));
+
+                let stroke_style = &mut raqote::StrokeStyle::default();
+                dt.stroke(
+                    &selection_path,
+                    &selection_source,
+                    stroke_style,
+                    &DrawOptions::new(),
+                );
+            }
+        }
+
+        // A construction site shows its health against what it will have when finished
+        let health_ratio = if building.is_constructed() {
+            building.health.health_ratio()
+        } else {
+            building.health.get_health() as f32 / building.get_definition().health as f32
+        };
+        draw_health_bar(
+            dt,
+            camera,
+            health_ratio,
+            &(building.get_position().as_vec2f()
+                + Vec2f::new(
+                    building.get_width() as f32 / 2.0,
+                    building.get_height() as f32 + 0.1,
+                )),
+            building.get_width() as f32,
+        );
+    }
+}
+
+// Greyed out ghosts of enemy buildings where the team last saw them, while out of sight
+fn draw_remembered_buildings(dt: &mut DrawTarget, camera: &Camera, visibility: &VisibilityGrid) {
+    for snapshot in visibility.get_remembered_buildings().iter() {
+        if visibility.is_area_visible(&snapshot.position, &snapshot.get_size()) {
+            continue;
+        }
+
+        let draw_pos = camera.world_to_screen(&Vec2f::new(
+            snapshot.position.x as f32 + 0.1,
+            snapshot.position.y as f32 + 0.1,
+        ));
+        let mut path_builder = PathBuilder::new();
+        path_builder.rect(
+            draw_pos.x,
+            draw_pos.y,
+            camera.length_to_pixels_x(snapshot.width as f32 - 0.2),
+            camera.length_to_pixels_y(snapshot.height as f32 - 0.2),
+        );
+
+        let source = if snapshot.team == 0 {
+            Source::Solid(SolidSource::from_unpremultiplied_argb(
+                0xa0, 0x90, 0xa8, 0x98,
+            ))
+        } else {
+            Source::Solid(SolidSource::from_unpremultiplied_argb(
+                0xa0, 0xa8, 0x90, 0x98,
+            ))
+        };
+
+        if snapshot.is_constructed {
+            dt.fill(&path_builder.finish(), &source, &DrawOptions::new());
+        } else {
+            dt.stroke(
+                &path_builder.finish(),
+                &source,
+                &raqote::StrokeStyle::default(),
+                &DrawOptions::new(),
+            );
+        }
+
+        draw_health_bar(
+            dt,
+            camera,
+            snapshot.health as f32 / snapshot.full_health as f32,
+            &(snapshot.position.as_vec2f()
+                + Vec2f::new(snapshot.width as f32 / 2.0, snapshot.height as f32 + 0.1)),
+            snapshot.width as f32,
+        );
+    }
+}
+
+// Footprint of a building that is being placed, green if it can be placed there and red if not
+pub fn draw_building_ghost(
+    game: &Game,
+    dt: &mut DrawTarget,
+    camera: &Camera,
+    top_left: &Vec2i,
+    building_type: BuildingType,
+) {
+    let definition = match game.get_building_registry().get(building_type) {
+        Some(definition) => definition,
+        None => return,
+    };
+
+    let draw_pos = camera.world_to_screen(&top_left.as_vec2f());
+    let mut path_builder = PathBuilder::new();
+    path_builder.move_to(draw_pos.x, draw_pos.y);
+    path_builder.line_to(
+        draw_pos.x + camera.length_to_pixels_x(definition.width as f32),
+        draw_pos.y,
+    );
+    path_builder.line_to(
+        draw_pos.x + camera.length_to_pixels_x(definition.width as f32),
+        draw_pos.y + camera.length_to_pixels_y(definition.height as f32),
+    );
+    path_builder.line_to(
+        draw_pos.x,
+        draw_pos.y + camera.length_to_pixels_y(definition.height as f32),
+    );
+    path_builder.close();
+
+    let source = if game
+        .check_building_placement(top_left, building_type)
+        .is_ok()
+    {
+        Source::Solid(SolidSource::from_unpremultiplied_argb(0x80, 0, 255, 0))
+    } else {
+        Source::Solid(SolidSource::from_unpremultiplied_argb(0x80, 255, 0, 0))
+    };
+    dt.fill(&path_builder.finish(), &source, &DrawOptions::new());
+}
+
+fn draw_entities(
+    game: &Game,
+    dt: &mut DrawTarget,
+    camera: &Camera,
+    selected_entiy_ids: &[Handle],
+    fog_team: Option<u8>,
+) {
+    let mut selection_path_builder = PathBuilder::new();
+    let mut goal_path = PathBuilder::new();
+    let mut entity_type_path_builder = PathBuilder::new();
+
+    let mut path_builder_0 = PathBuilder::new();
+    let mut path_builder_1 = PathBuilder::new();
+    let mut path_builder_2 = PathBuilder::new();
+    let mut path_builder_3 = PathBuilder::new();
+
+    let mut path_builders = [
+        &mut path_builder_0,
+        &mut path_builder_1,
+        &mut path_builder_2,
+        &mut path_builder_3,
+    ];
+
+    let (min_x, max_x, min_y, max_y) = get_draw_boundaries(game, camera);
+
+    for entity_ref in game.get_entity_container().entities_in_box(
+        Vec2f::new(min_x as f32, min_y as f32),
+        Vec2f::new(max_x as f32, max_y as f32),
+        EntityFilter::empty(),
+    ) {
+        let entity = entity_ref.borrow();
+        if !game.is_entity_seen_by(&entity, fog_team) {
+            continue;
+        }
+        let entity_position = entity.get_position();
+
+        let draw_pos = camera.world_to_screen(&Vec2f::new(entity_position.x, entity_position.y));
+
+        let path_builder = &mut path_builders[entity.get_team() as usize];
+        let radius = entity.get_radius() - 0.0;
+
+        // path_builder.move_to(draw_pos.x, draw_pos.y);
+        // path_builder.arc(
+        //     draw_pos.x,
+        //     draw_pos.y,
+        //     camera.length_to_pixels(radius),
+        //     0.0,
+        //     2.0 * std::f32::consts::PI,
+        // );
+
+        // Draw an ellipsis
+        let delt_x = camera.length_to_pixels_x(radius);
+        let delt_y = camera.length_to_pixels_y(radius);
+        let cubic_rate = 0.55228; // https://stackoverflow.com/questions/1734745/how-to-create-circle-with-b%C3%A9zier-curves
+        path_builder.move_to(draw_pos.x, draw_pos.y - delt_y);
+        path_builder.cubic_to(
+            draw_pos.x + delt_x * cubic_rate,
+            draw_pos.y - delt_y,
+            draw_pos.x + delt_x,
+            draw_pos.y - delt_y * cubic_rate,
+            draw_pos.x + delt_x,
+            draw_pos.y,
+        );
+        path_builder.cubic_to(
+            draw_pos.x + delt_x,
+            draw_pos.y + delt_y * cubic_rate,
+            draw_pos.x + delt_x * cubic_rate,
+            draw_pos.y + delt_y,
+            draw_pos.x,
+            draw_pos.y + delt_y,
+        );
+        path_builder.cubic_to(
+            draw_pos.x - delt_x * cubic_rate,
+            draw_pos.y + delt_y,
+            draw_pos.x - delt_x,
+            draw_pos.y + delt_y * cubic_rate,
+            draw_pos.x - delt_x,
+            draw_pos.y,
+        );
+        path_builder.cubic_to(
+            draw_pos.x - delt_x,
+            draw_pos.y - delt_y * cubic_rate,
+            draw_pos.x - delt_x * cubic_rate,
+            draw_pos.y - delt_y,
+            draw_pos.x,
+            draw_pos.y - delt_y,
+        );
+        // path_builder.quad_to( draw_pos.x + delt_x, draw_pos.y + delt_y, draw_pos.x, draw_pos.y + delt_y, );
+
+        // path_builder.quad_to( draw_pos.x - delt_x, draw_pos.y, draw_pos.x, draw_pos.y - delt_y, );
+        path_builder.close();
+
+        let delt_x = camera.length_to_pixels_x(radius) * 0.5;
+        let delt_y = camera.length_to_pixels_y(radius) * 0.5;
+
+        match entity.get_definition().shape {
+            EntityShape::Triangle => {
+                entity_type_path_builder.move_to(draw_pos.x, draw_pos.y - delt_y);
+                entity_type_path_builder
+                    .line_to(draw_pos.x + delt_x * 0.81, draw_pos.y + delt_y * 0.58);
+                entity_type_path_builder
+                    .line_to(draw_pos.x - delt_x * 0.81, draw_pos.y + delt_y * 0.58);
+                entity_type_path_builder.close();
+            }
+            EntityShape::Square => {
+                entity_type_path_builder
+                    .move_to(draw_pos.x - delt_x * 0.707, draw_pos.y - delt_y * 0.707);
+                entity_type_path_builder
+                    .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y - delt_y * 0.707);
+                entity_type_path_builder
+                    .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y + delt_y * 0.707);
+                entity_type_path_builder
+                    .line_to(draw_pos.x - delt_x * 0.707, draw_pos.y + delt_y * 0.707);
+                entity_type_path_builder.close();
+            }
+            EntityShape::Bar => {
+                entity_type_path_builder
+                    .move_to(draw_pos.x - delt_x * 0.707, draw_pos.y - delt_y * 0.2);
+                entity_type_path_builder
+                    .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y - delt_y * 0.2);
+                entity_type_path_builder
+                    .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y + delt_y * 0.2);
+                entity_type_path_builder
+                    .line_to(draw_pos.x - delt_x * 0.707, draw_pos.y + delt_y * 0.2);
+                entity_type_path_builder.close();
+            }
+            EntityShape::Diamond => {
+                entity_type_path_builder.move_to(draw_pos.x, draw_pos.y - delt_y);
+                entity_type_path_builder.line_to(draw_pos.x + delt_x, draw_pos.y);
+                entity_type_path_builder.line_to(draw_pos.x, draw_pos.y + delt_y);
+                entity_type_path_builder.line_to(draw_pos.x - delt_x, draw_pos.y);
+                entity_type_path_builder.close();
+            }
+            EntityShape::Cross => {
+                // Two overlapping bars
+                entity_type_path_builder
+                    .move_to(draw_pos.x - delt_x * 0.707, draw_pos.y - delt_y * 0.2);
+                entity_type_path_builder
+                    .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y - delt_y * 0.2);
+                entity_type_path_builder
+                    .line_to(draw_pos.x + delt_x * 0.707, draw_pos.y + delt_y * 0.2);
+                entity_type_path_builder
+                    .line_to(draw_pos.x - delt_x * 0.707, draw_pos.y + delt_y * 0.2);
+                entity_type_path_builder.close();
+                entity_type_path_builder
+                    .move_to(draw_pos.x - delt_x * 0.2, draw_pos.y - delt_y * 0.707);
+                entity_type_path_builder
+                    .line_to(draw_pos.x + delt_x * 0.2, draw_pos.y - delt_y * 0.707);
+                entity_type_path_builder
+                    .line_to(draw_pos.x + delt_x * 0.2, draw_pos.y + delt_y * 0.707);
+                entity_type_path_builder
+                    .line_to(draw_pos.x - delt_x * 0.2, draw_pos.y + delt_y * 0.707);
+                entity_type_path_builder.close();
+            }
+        }
+
+        if selected_entiy_ids.contains(&entity.get_id()) {
+            if let Some(goal) = entity.get_goal() {
+                let goal_pos = camera.world_to_screen(&Vec2f::new(goal.x, goal.y));
+
+                goal_path.move_to(draw_pos.x, draw_pos.y);
+                goal_path.line_to(goal_pos.x, goal_pos.y);
+            }
+
+            let top_right_corner = camera.world_to_screen(&Vec2f::new(
+                entity_position.x - radius,
+                entity_position.y - radius,
+            ));
+
+            selection_path_builder.rect(
+                top_right_corner.x,
+                top_right_corner.y,
+                camera.length_to_pixels_x(radius * 2.0),
+                camera.length_to_pixels_y(radius * 2.0),
+            );
+        }
+    }
+
+    dt.fill(
+        &path_builder_0.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(
+            255, 0x7d, 0xde, 0x92,
+        )),
+        &DrawOptions::new(),
+    );
+    dt.fill(
+        &path_builder_1.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(
+            255, 0xde, 0x7d, 0x92,
+        )),
+        &DrawOptions::new(),
+    );
+    dt.fill(
+        &path_builder_2.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(
+            255, 0xde, 0x92, 0x7d,
+        )),
+        &DrawOptions::new(),
+    );
+    dt.fill(
+        &path_builder_3.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(
+            255, 0x92, 0xde, 0x7d,
+        )),
+        &DrawOptions::new(),
+    );
+
+    let selection_path = selection_path_builder.finish();
+    let selection_source = Source::Solid(SolidSource::from_unpremultiplied_argb(255, 0, 255, 0));
+
+    let stroke_style = &mut raqote::StrokeStyle::default();
+    dt.stroke(
+        &selection_path,
+        &selection_source,
+        stroke_style,
+        &DrawOptions::new(),
+    );
+
+    dt.stroke(
+        &goal_path.finish(),
+        &selection_source,
+        stroke_style,
+        &DrawOptions::new(),
+    );
+
+    let entity_type_path = entity_type_path_builder.finish();
+    dt.fill(
+        &entity_type_path,
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
+        // stroke_style,
+        &DrawOptions::new(),
+    );
+
+    for entity_ref in game.get_entity_container().iter_alive() {
+        let entity = entity_ref.borrow();
+        if !game.is_entity_seen_by(&entity, fog_team) {
+            continue;
+        }
+        let entity_position = entity.get_position();
+
+        let health_ratio = entity.health.health_ratio();
+        if health_ratio < 1.0 {
+            draw_health_bar(
+                dt,
+                camera,
+                health_ratio,
+                &(entity_position + Vec2f::new(0.0, entity.get_radius() + 0.1)),
+                entity.get_radius() * 2.0,
+            );
+        }
+    }
+}
+
+fn get_draw_boundaries(game: &Game, camera: &Camera) -> (i32, i32, i32, i32) {
+    let top_left = camera.screen_to_world(&Vec2f::new(0.0, 0.0));
+    let bottom_right =
+        camera.screen_to_world(&Vec2f::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32));
+
+    let min_x = 0.max(top_left.x as i32);
+    let max_x = game.ground.get_width().min(bottom_right.x as i32 + 1);
+    let min_y = 0.max(top_left.y as i32);
+    let max_y = game.ground.get_height().min(bottom_right.y as i32 + 1);
+
+    (min_x, max_x, min_y, max_y)
+}
+
+// With the fog of war, explored tiles are drawn as they were last seen
+fn draw_ground(
+    game: &Game,
+    dt: &mut DrawTarget,
+    camera: &Camera,
+    visibility: Option<&VisibilityGrid>,
+) {
+    let mut ground_path_builder = PathBuilder::new();
+    let mut wall_path_builder = PathBuilder::new();
+    let mut gold_path_builder = PathBuilder::new();
+    let mut tree_path_builder = PathBuilder::new();
+    let mut quarry_path_builder = PathBuilder::new();
+
+    let (min_x, max_x, min_y, max_y) = get_draw_boundaries(game, camera);
+
+    for x in min_x..max_x {
+        for y in min_y..max_y {
+            let ground_type = match visibility {
+                Some(visibility) => visibility.get_known_ground_at(&game.ground, x, y),
+                None => game.ground.get_at(x, y),
+            };
+            match ground_type {
+                GroundType::Empty | GroundType::Gold | GroundType::Tree | GroundType::Quarry => {
+                    let draw_pos = camera.world_to_screen(&Vec2f::new(x as f32, y as f32));
+                    ground_path_builder.move_to(draw_pos.x, draw_pos.y);
+                    ground_path_builder
+                        .line_to(draw_pos.x + camera.length_to_pixels_x(1.0), draw_pos.y);
+                    ground_path_builder.line_to(
+                        draw_pos.x + camera.length_to_pixels_x(1.0),
+                        draw_pos.y + camera.length_to_pixels_y(1.0),
+                    );
+                    ground_path_builder
+                        .line_to(draw_pos.x, draw_pos.y + camera.length_to_pixels_y(1.0));
+                    ground_path_builder.close();
+                }
+                GroundType::Wall => {
+                    let draw_pos = camera.world_to_screen(&Vec2f::new(x as f32, y as f32));
+                    wall_path_builder.move_to(draw_pos.x, draw_pos.y);
+                    wall_path_builder
+                        .line_to(draw_pos.x + camera.length_to_pixels_x(1.0), draw_pos.y);
+                    wall_path_builder.line_to(
+                        draw_pos.x + camera.length_to_pixels_x(1.0),
+                        draw_pos.y + camera.length_to_pixels_y(1.0),
+                    );
+                    wall_path_builder
+                        .line_to(draw_pos.x, draw_pos.y + camera.length_to_pixels_y(1.0));
+                    wall_path_builder.close();
+                }
+            }
+            let resource_path_builder = match ground_type {
+                GroundType::Gold => Some(&mut gold_path_builder),
+                GroundType::Tree => Some(&mut tree_path_builder),
+                GroundType::Quarry => Some(&mut quarry_path_builder),
+                GroundType::Empty | GroundType::Wall => None,
+            };
+            if let Some(resource_path_builder) = resource_path_builder {
+                for (xx, yy) in [
+                    // (0.15, 0.0),
+                    // (0.85, 0.0),
+                    // (0.15, 0.7),
+                    // (0.85, 0.7),
+                    (0.15 + 0.2, 0.0 + 0.1),
+                    (0.85 - 0.1, 0.0 + 0.2),
+                    (0.15 + 0.2, 0.7 - 0.3),
+                    (0.85 - 0.2, 0.7 - 0.1),
+                ] {
+                    let draw_pos =
+                        camera.world_to_screen(&Vec2f::new(x as f32 + xx, y as f32 + yy));
+                    resource_path_builder.move_to(draw_pos.x, draw_pos.y);
+                    resource_path_builder.line_to(
+                        draw_pos.x + camera.length_to_pixels_x(0.15),
+                        draw_pos.y + camera.length_to_pixels_y(0.3),
+                    );
+                    resource_path_builder.line_to(
+                        draw_pos.x - camera.length_to_pixels_x(0.15),
+                        draw_pos.y + camera.length_to_pixels_y(0.3),
+                    );
+                    // gold_path_builder.line_to(draw_pos.x, draw_pos.y + camera.length_to_pixels(1.0));
+                    resource_path_builder.close();
+                }
+            }
+        }
+    }
+
+    let ground_path = ground_path_builder.finish();
+    let wall_path = wall_path_builder.finish();
+    let wall_source = Source::Solid(SolidSource::from_unpremultiplied_argb(
+        255, 0x89, 0x99, 0xa6,
+    ));
+    let ground_source = Source::Solid(SolidSource::from_unpremultiplied_argb(
+        255, 0x48, 0x40, 0x41,
+    ));
+    let gold_source = Source::Solid(SolidSource::from_unpremultiplied_argb(
+        255, 0xff, 0xd7, 0x00,
+    ));
+    let tree_source = Source::Solid(SolidSource::from_unpremultiplied_argb(
+        255, 0x2e, 0x8b, 0x57,
+    ));
+    let quarry_source = Source::Solid(SolidSource::from_unpremultiplied_argb(
+        255, 0xc0, 0xc0, 0xc0,
+    ));
+
+    dt.fill(&ground_path, &ground_source, &DrawOptions::new());
+    dt.fill(&wall_path, &wall_source, &DrawOptions::new());
+    dt.fill(
+        &gold_path_builder.finish(),
+        &gold_source,
+        &DrawOptions::new(),
+    );
+    dt.fill(
+        &tree_path_builder.finish(),
+        &tree_source,
+        &DrawOptions::new(),
+    );
+    dt.fill(
+        &quarry_path_builder.finish(),
+        &quarry_source,
+        &DrawOptions::new(),
+    );
+}
+
+// Tiles that were never seen are black and explored tiles that are not seen right now are darkened
+fn draw_fog(game: &Game, dt: &mut DrawTarget, camera: &Camera, visibility: &VisibilityGrid) {
+    let mut hidden_path_builder = PathBuilder::new();
+    let mut explored_path_builder = PathBuilder::new();
+
+    let (min_x, max_x, min_y, max_y) = get_draw_boundaries(game, camera);
+
+    for x in min_x..max_x {
+        for y in min_y..max_y {
+            let path_builder = match visibility.get_at(x, y) {
+                Visibility::Hidden => &mut hidden_path_builder,
+                Visibility::Explored => &mut explored_path_builder,
+                Visibility::Visible => continue,
+            };
+            let draw_pos = camera.world_to_screen(&Vec2f::new(x as f32, y as f32));
+            path_builder.rect(
+                draw_pos.x,
+                draw_pos.y,
+                camera.length_to_pixels_x(1.0),
+                camera.length_to_pixels_y(1.0),
+            );
+        }
+    }
+
+    dt.fill(
+        &hidden_path_builder.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 0, 0, 0)),
+        &DrawOptions::new(),
+    );
+    dt.fill(
+        &explored_path_builder.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(0x80, 0, 0, 0)),
+        &DrawOptions::new(),
+    );
+}
+
+pub fn draw_debug_path(game: &Game, dt: &mut DrawTarget, camera: &Camera) {
+    if let Some(debug_path) = &game.debug_path {
+        let mut path_builder = PathBuilder::new();
+
+        let (min_x, max_x, min_y, max_y) = get_draw_boundaries(game, camera);
+
+        let debug_path = debug_path.borrow();
+        for x in min_x..max_x {
+            for y in min_y..max_y {
+                let path_item = (x, y);
+                if let Some(direction) = debug_path.position_datas.get(&path_item) {
+                    let center_pos = camera.world_to_screen(&Vec2f::new(
+                        path_item.0 as f32 + 0.5,
+                        path_item.1 as f32 + 0.5,
+                    ));
+
+                    path_builder.move_to(
+                        center_pos.x - camera.length_to_pixels_x(direction.x * 0.2),
+                        center_pos.y - camera.length_to_pixels_y(direction.y * 0.2),
+                    );
+                    path_builder.line_to(
+                        center_pos.x + camera.length_to_pixels_x(direction.x * 0.2),
+                        center_pos.y + camera.length_to_pixels_y(direction.y * 0.2),
+                    );
+                    path_builder.line_to(
+                        center_pos.x
+                            + camera.length_to_pixels_x(
+                                direction.x * 0.2 + direction.y * 0.1 - direction.x * 0.1,
+                            ),
+                        center_pos.y
+                            + camera.length_to_pixels_y(
+                                direction.y * 0.2 - direction.x * 0.1 - direction.y * 0.1,
+                            ),
+                    );
+                    path_builder.move_to(
+                        center_pos.x + camera.length_to_pixels_x(direction.x * 0.2),
+                        center_pos.y + camera.length_to_pixels_y(direction.y * 0.2),
+                    );
+                    path_builder.line_to(
+                        center_pos.x
+                            + camera.length_to_pixels_x(
+                                direction.x * 0.2 - direction.y * 0.1 - direction.x * 0.1,
+                            ),
+                        center_pos.y
+                            + camera.length_to_pixels_y(
+                                direction.y * 0.2 + direction.x * 0.1 - direction.y * 0.1,
+                            ),
+                    );
+                }
+            }
+        }
+
+        let path = path_builder.finish();
+        let source = Source::Solid(SolidSource::from_unpremultiplied_argb(
+            255, 0x38, 0x30, 0x31,
+        ));
+        let stroke_style = &mut raqote::StrokeStyle::default();
+        dt.stroke(&path, &source, stroke_style, &DrawOptions::new());
+    }
+}
+
+pub fn draw(
+    game: &Game,
+    dt: &mut DrawTarget,
+    camera: &Camera,
+    selected_entiy_ids: &[Handle],
+    selected_building_id: &Option<Handle>,
+    fog_team: Option<u8>,
+) {
+    let visibility = fog_team.and_then(|team_id| game.get_team_visibility(team_id));
+    draw_ground(game, dt, camera, visibility);
+    if let Some(visibility) = visibility {
+        draw_fog(game, dt, camera, visibility);
+    }
+    draw_entities(game, dt, camera, selected_entiy_ids, fog_team);
+    draw_buildings(game, dt, camera, selected_building_id, fog_team);
+    draw_projectiles(game, dt, camera, fog_team);
+    draw_debug_path(game, dt, camera);
+    draw_overlay(game, dt, selected_building_id, fog_team);
+    draw_minimap(game, dt, camera, fog_team);
+}
+
+// The whole ground with the fog of the shown team, units and buildings as dots and boxes in their
+// team's colour, and the part of the map that is on screen
+fn draw_minimap(game: &Game, dt: &mut DrawTarget, camera: &Camera, fog_team: Option<u8>) {
+    dt.fill_rect(
+        MINIMAP_LEFT,
+        MINIMAP_TOP,
+        MINIMAP_SIZE,
+        MINIMAP_SIZE,
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 64, 64, 64)),
+        &DrawOptions::new(),
+    );
+
+    let tile_size = minimap_tile_size();
+    let visibility = fog_team.and_then(|team_id| game.get_team_visibility(team_id));
+
+    let mut ground_path_builder = PathBuilder::new();
+    let mut wall_path_builder = PathBuilder::new();
+    let mut gold_path_builder = PathBuilder::new();
+    let mut tree_path_builder = PathBuilder::new();
+    let mut quarry_path_builder = PathBuilder::new();
+    let mut hidden_path_builder = PathBuilder::new();
+    let mut explored_path_builder = PathBuilder::new();
+
+    for x in 0..GROUND_WIDTH {
+        for y in 0..GROUND_HEIGHT {
+            let draw_pos = world_to_minimap(&Vec2f::new(x as f32, y as f32));
+            // Explored tiles show the ground as it was last seen, like the main view
+            let ground_type = match visibility {
+                Some(visibility) => visibility.get_known_ground_at(&game.ground, x, y),
+                None => game.ground.get_at(x, y),
+            };
+            let path_builder = match ground_type {
+                GroundType::Empty => &mut ground_path_builder,
+                GroundType::Wall => &mut wall_path_builder,
+                GroundType::Gold => &mut gold_path_builder,
+                GroundType::Tree => &mut tree_path_builder,
+                GroundType::Quarry => &mut quarry_path_builder,
+            };
+            path_builder.rect(draw_pos.x, draw_pos.y, tile_size.x, tile_size.y);
+
+            let fog_path_builder = match visibility.map(|visibility| visibility.get_at(x, y)) {
+                Some(Visibility::Hidden) => &mut hidden_path_builder,
+                Some(Visibility::Explored) => &mut explored_path_builder,
+                Some(Visibility::Visible) | None => continue,
+            };
+            fog_path_builder.rect(draw_pos.x, draw_pos.y, tile_size.x, tile_size.y);
+        }
+    }
+
+    for (path_builder, (r, g, b)) in [
+        (ground_path_builder, (0x48, 0x40, 0x41)),
+        (wall_path_builder, (0x89, 0x99, 0xa6)),
+        (gold_path_builder, (0xff, 0xd7, 0x00)),
+        (tree_path_builder, (0x2e, 0x8b, 0x57)),
+        (quarry_path_builder, (0xc0, 0xc0, 0xc0)),
+    ] {
+        dt.fill(
+            &path_builder.finish(),
+            &Source::Solid(SolidSource::from_unpremultiplied_argb(255, r, g, b)),
+            &DrawOptions::new(),
+        );
+    }
+    dt.fill(
+        &hidden_path_builder.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 0, 0, 0)),
+        &DrawOptions::new(),
+    );
+    dt.fill(
+        &explored_path_builder.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(0x80, 0, 0, 0)),
+        &DrawOptions::new(),
+    );
+
+    let mut ghost_path_builder = PathBuilder::new();
+    let mut team_path_builders = [PathBuilder::new(), PathBuilder::new()];
+
+    if let Some(visibility) = visibility {
+        for snapshot in visibility.get_remembered_buildings().iter() {
+            if visibility.is_area_visible(&snapshot.position, &snapshot.get_size()) {
+                continue;
+            }
+            let draw_pos = world_to_minimap(&snapshot.position.as_vec2f());
+            ghost_path_builder.rect(
+                draw_pos.x,
+                draw_pos.y,
+                snapshot.width as f32 * tile_size.x,
+                snapshot.height as f32 * tile_size.y,
+            );
+        }
+    }
+
+    for building in game.get_building_container().get_buildings().iter() {
+        let building = building.borrow();
+        if !game.is_building_seen_by(&building, fog_team) {
+            continue;
+        }
+        let draw_pos = world_to_minimap(&building.get_position().as_vec2f());
+        team_path_builders[building.get_team().min(1) as usize].rect(
+            draw_pos.x,
+            draw_pos.y,
+            building.get_width() as f32 * tile_size.x,
+            building.get_height() as f32 * tile_size.y,
+        );
+    }
+
+    // Units are at least two pixels wide to stay visible
+    for entity in game.get_entity_container().iter_alive() {
+        let entity = entity.borrow();
+        if !game.is_entity_seen_by(&entity, fog_team) {
+            continue;
+        }
+        let draw_pos = world_to_minimap(&entity.get_position());
+        let size = (entity.get_definition().radius * 2.0 * tile_size.x).max(2.0);
+        team_path_builders[entity.get_team().min(1) as usize].rect(
+            draw_pos.x - size / 2.0,
+            draw_pos.y - size / 2.0,
+            size,
+            size,
+        );
+    }
+
+    dt.fill(
+        &ghost_path_builder.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(
+            0xa0, 0x9c, 0x9c, 0x98,
+        )),
+        &DrawOptions::new(),
+    );
+    for (path_builder, (r, g, b)) in team_path_builders
+        .into_iter()
+        .zip([(0x7d, 0xde, 0x92), (0xde, 0x7d, 0x92)])
+    {
+        dt.fill(
+            &path_builder.finish(),
+            &Source::Solid(SolidSource::from_unpremultiplied_argb(255, r, g, b)),
+            &DrawOptions::new(),
+        );
+    }
+
+    // The screen's corners are clamped to the map
+    let clamp_to_map = |position: Vec2f| {
+        world_to_minimap(&Vec2f::new(
+            position.x.clamp(0.0, GROUND_WIDTH as f32),
+            position.y.clamp(0.0, GROUND_HEIGHT as f32),
+        ))
+    };
+    let view_top_left = clamp_to_map(camera.screen_to_world(&Vec2f::new(0.0, 0.0)));
+    let view_bottom_right = clamp_to_map(
+        camera.screen_to_world(&Vec2f::new(SCREEN_WIDTH as f32, SCREEN_HEIGHT as f32)),
+    );
+    let mut view_path_builder = PathBuilder::new();
+    view_path_builder.rect(
+        view_top_left.x,
+        view_top_left.y,
+        view_bottom_right.x - view_top_left.x,
+        view_bottom_right.y - view_top_left.y,
+    );
+    dt.stroke(
+        &view_path_builder.finish(),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
+        &raqote::StrokeStyle::default(),
+        &DrawOptions::new(),
+    );
+}
+
+pub fn draw_overlay(
+    game: &Game,
+    dt: &mut DrawTarget,
+    selected_building_id: &Option<Handle>,
+    fog_team: Option<u8>,
+) {
+    let font = font_kit::loader::Loader::from_file(
+        &mut std::fs::File::open("res/Roboto-Medium.ttf").unwrap(),
+        0,
+    )
+    .unwrap();
+
+    for (i, team) in game.get_teams().iter().enumerate() {
+        dt.draw_text(
+            &font,
+            20.,
+            &format!(
+                "Team {} Gold: {} Wood: {} Stone: {} Supply: {}/{}",
+                team.get_id(),
+                team.get_resources().gold,
+                team.get_resources().wood,
+                team.get_resources().stone,
+                team.get_supply_used(),
+                team.get_supply_cap()
+            ),
+            // "3",
+            Point::new(0., 20. + 20. * i as f32),
+            &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
+            &DrawOptions::new(),
+        );
+    }
+
+    let fog_text = match fog_team {
+        Some(team_id) => format!("Fog of war: Team {}", team_id),
+        None => "Fog of war: off".to_string(),
+    };
+    dt.draw_text(
+        &font,
+        20.,
+        &fog_text,
+        Point::new(0., 20. + 20. * game.get_teams().len() as f32),
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
+        &DrawOptions::new(),
+    );
+
+    dt.fill_rect(
+        0.,
+        SCREEN_HEIGHT as f32 - 180.,
+        SCREEN_WIDTH as f32,
+        180.,
+        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 128, 128, 128)),
+        &DrawOptions::new(),
+    );
+
+    if let Some(selected_building_id) = selected_building_id {
+        if let Some(building_ref) = game
+            .get_building_container()
+            .get_building_by_id(*selected_building_id)
+        {
+            let building = building_ref.borrow();
+
+            dt.draw_text(
+                &font,
+                20.,
+                &format!("{:?}", building.get_building_type()),
+                Point::new(180., SCREEN_HEIGHT as f32 - 170. + 20.),
+                &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
+                &DrawOptions::new(),
+            );
+
+            if building.is_constructed() {
+                let spawn_queue = building.get_spawn_queue().clone();
+                let spawn_timer = building.get_spawn_timer();
+                for (i, spawn_item) in spawn_queue.iter().enumerate() {
+                    dt.draw_text(
+                        &font,
+                        20.,
+                        spawn_item.entity_type.get_name(),
+                        Point::new(SPAWN_QUEUE_X, spawn_queue_item_baseline(i)),
+                        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
+                        &DrawOptions::new(),
+                    );
+                }
+                if !spawn_queue.is_empty() {
+                    let spawn_duratoin = building.get_spawn_duration();
+                    dt.fill_rect(
+                        180. + 100.,
+                        SCREEN_HEIGHT as f32 - 170. + 22.,
+                        100.,
+                        20.,
+                        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 64, 64, 64)),
+                        &DrawOptions::new(),
+                    );
+                    dt.fill_rect(
+                        180. + 100.,
+                        SCREEN_HEIGHT as f32 - 170. + 22.,
+                        100. * (spawn_timer as f32 / spawn_duratoin as f32),
+                        20.,
+                        &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 0, 255, 0)),
+                        &DrawOptions::new(),
+                    );
+                }
+            } else {
+                dt.draw_text(
+                    &font,
+                    20.,
+                    &format!(
+                        "Under construction: {}%",
+                        (building.construction_progress.health_ratio() * 100.0) as i32
+                    ),
+                    Point::new(180., SCREEN_HEIGHT as f32 - 170. + 20. + 20.),
+                    &Source::Solid(SolidSource::from_unpremultiplied_argb(255, 255, 255, 255)),
+                    &DrawOptions::new(),
+                );
+            }
+        }
+    }
+}
@@ -1,9 +1,5 @@
 // use pixels::wgpu;
-use winit::{
-    event::*,
-    event_loop::{ControlFlow, EventLoop},
-    window::WindowBuilder,
-};
+use winit::event::*;
 // import bytemuck
 use wgpu::util::DeviceExt;
 
@@ -144,8 +140,7 @@ impl Graphics {
         // sRGB surfaces, you'll need to account for that when drawing to the frame.
         let surface_format = surface_caps.formats.iter()
             .copied()
-            .filter(|f| f.is_srgb())
-            .next()
+            .find(|f| f.is_srgb())
             .unwrap_or(surface_caps.formats[0]);
         let config = wgpu::SurfaceConfiguration {
             usage: wgpu::TextureUsages::RENDER_ATTACHMENT,
@@ -363,6 +358,7 @@ impl Graphics {
         }
     }
 
+    #[allow(clippy::match_single_binding)]
     pub fn input(&mut self, event: &WindowEvent) -> bool {
         match event {
             // WindowEvent::CursorMoved { position, device_id, modifiers } => {
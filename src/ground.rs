@@ -1,39 +1,72 @@
-use crate::constants::{GROUND_HEIGHT, GROUND_WIDTH};
-use crate::vec::Vec2f;
+use crate::constants::{GROUND_HEIGHT, GROUND_WIDTH, RESOURCE_DEPOSIT_AMOUNT};
+use crate::resources::ResourceType;
+use crate::rng::GameRng;
+use crate::vec::{Vec2f, Vec2i};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
 
 // Derive clone
 
-#[derive(Clone, PartialEq)]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum GroundType {
     Empty,
     Wall,
     Gold,
+    Tree,
+    Quarry,
 }
 
+impl GroundType {
+    // What workers get from gathering here
+    pub fn get_resource_type(&self) -> Option<ResourceType> {
+        match self {
+            GroundType::Gold => Some(ResourceType::Gold),
+            GroundType::Tree => Some(ResourceType::Wood),
+            GroundType::Quarry => Some(ResourceType::Stone),
+            GroundType::Empty | GroundType::Wall => None,
+        }
+    }
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Ground {
     tiles: Vec<GroundType>,
+    resource_amounts: Vec<i32>, // What is left of each resource tile
     width: i32,
     height: i32,
 }
 
 impl Ground {
-    pub fn new() -> Ground {
+    pub fn new(rng: &mut GameRng) -> Ground {
         let width: i32 = GROUND_WIDTH;
         let height: i32 = GROUND_HEIGHT;
         let mut tiles: Vec<GroundType> = Vec::new();
         for _ in 0..width * height {
             // Random change of being a wall
-            if rand::random::<f32>() < 0.1 {
+            if rng.gen::<f32>() < 0.1 {
                 tiles.push(GroundType::Wall);
-            } else if rand::random::<f32>() < 0.02 {
+            } else if rng.gen::<f32>() < 0.02 {
                 tiles.push(GroundType::Gold);
+            } else if rng.gen::<f32>() < 0.04 {
+                tiles.push(GroundType::Tree);
+            } else if rng.gen::<f32>() < 0.01 {
+                tiles.push(GroundType::Quarry);
             } else {
                 tiles.push(GroundType::Empty);
             }
             // tiles.push(GroundType::Empty);
         }
+        let resource_amounts = tiles
+            .iter()
+            .map(|tile| match tile.get_resource_type() {
+                Some(_) => RESOURCE_DEPOSIT_AMOUNT,
+                None => 0,
+            })
+            .collect();
         let mut ground = Ground {
             tiles,
+            resource_amounts,
             width,
             height,
         };
@@ -64,7 +97,56 @@ impl Ground {
             println!("Ground set_at out of bounds");
             return;
         }
-        self.tiles[(y * self.width + x) as usize] = ground_type;
+        let index = (y * self.width + x) as usize;
+        self.resource_amounts[index] = match ground_type.get_resource_type() {
+            Some(_) => RESOURCE_DEPOSIT_AMOUNT,
+            None => 0,
+        };
+        self.tiles[index] = ground_type;
+    }
+
+    pub fn get_resource_amount_at(&self, x: i32, y: i32) -> i32 {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return 0;
+        }
+        self.resource_amounts[(y * self.width + x) as usize]
+    }
+
+    // Returns how much was actually taken. The tile becomes empty when it runs out.
+    pub fn take_resource(&mut self, x: i32, y: i32, amount: i32) -> i32 {
+        if self.get_at(x, y).get_resource_type().is_none() {
+            return 0;
+        }
+        let index = (y * self.width + x) as usize;
+        let taken = amount.min(self.resource_amounts[index]);
+        self.resource_amounts[index] -= taken;
+        if self.resource_amounts[index] <= 0 {
+            self.set_at(x, y, GroundType::Empty);
+        }
+        taken
+    }
+
+    // Closest tile that still gives the resource type, by straight line distance
+    pub fn find_closest_resource(
+        &self,
+        position: &Vec2i,
+        resource_type: ResourceType,
+    ) -> Option<Vec2i> {
+        let mut closest_distance = i32::MAX;
+        let mut closest_position: Option<Vec2i> = None;
+        for y in 0..self.height {
+            for x in 0..self.width {
+                if self.get_at(x, y).get_resource_type() != Some(resource_type) {
+                    continue;
+                }
+                let distance = (x - position.x).pow(2) + (y - position.y).pow(2);
+                if distance < closest_distance {
+                    closest_distance = distance;
+                    closest_position = Some(Vec2i::new(x, y));
+                }
+            }
+        }
+        closest_position
     }
 
     pub fn get_at(&self, x: i32, y: i32) -> GroundType {
@@ -85,8 +167,70 @@ impl Ground {
     pub fn blocked_at(&self, x: i32, y: i32) -> bool {
         match self.get_at(x, y) {
             GroundType::Empty => false,
-            GroundType::Wall | GroundType::Gold => true,
+            GroundType::Wall | GroundType::Gold | GroundType::Tree | GroundType::Quarry => true,
+        }
+    }
+
+    // Whether nothing blocks the straight line between the two positions. The tiles of the two
+    // ends are not checked, so that e.g. the wall or building that is looked at can be seen.
+    pub fn has_line_of_sight(&self, from: &Vec2f, to: &Vec2f) -> bool {
+        let start = from.as_vec2i();
+        let end = to.as_vec2i();
+        all_tiles_on_line(from, to, |tile| {
+            *tile == start || *tile == end || !self.blocked_at(tile.x, tile.y)
+        })
+    }
+
+    // Where to walk next to get to a tile within `range` of the target that has a line of sight
+    // to it, e.g. for a ranged unit to get around a wall. The closest such tile by walking
+    // distance is searched, not further than `max_distance` tiles away, and the furthest tile on
+    // the way there that can be walked to in a straight line is returned.
+    pub fn find_line_of_sight_step(
+        &self,
+        from: &Vec2f,
+        target: &Vec2f,
+        range: f32,
+        max_distance: i32,
+    ) -> Option<Vec2f> {
+        let start = from.as_vec2i();
+        let size = max_distance * 2 + 1;
+        let index = |tile: &Vec2i| {
+            ((tile.y - start.y + max_distance) * size + tile.x - start.x + max_distance) as usize
+        };
+        let tile_center = |tile: &Vec2i| Vec2f::new(tile.x as f32 + 0.5, tile.y as f32 + 0.5);
+
+        // Breadth first, so the first tile found is the closest
+        let mut came_from: Vec<Option<Vec2i>> = vec![None; (size * size) as usize];
+        came_from[index(&start)] = Some(start.clone());
+        let mut queue = VecDeque::new();
+        queue.push_back(start.clone());
+        while let Some(tile) = queue.pop_front() {
+            let position = tile_center(&tile);
+            if (position.clone() - target.clone()).length() <= range
+                && self.has_line_of_sight(&position, target)
+            {
+                // Walk back until the tile can be seen from the start
+                let mut step = tile;
+                while step != start && !self.has_line_of_sight(from, &tile_center(&step)) {
+                    step = came_from[index(&step)].clone()?;
+                }
+                return Some(tile_center(&step));
+            }
+
+            for (dx, dy) in [(1, 0), (-1, 0), (0, 1), (0, -1)] {
+                let next = Vec2i::new(tile.x + dx, tile.y + dy);
+                if (next.x - start.x).abs() > max_distance
+                    || (next.y - start.y).abs() > max_distance
+                    || self.blocked_at(next.x, next.y)
+                    || came_from[index(&next)].is_some()
+                {
+                    continue;
+                }
+                came_from[index(&next)] = Some(tile.clone());
+                queue.push_back(next);
+            }
         }
+        None
     }
 
     pub fn nearest_unblocked(&self, pos: &Vec2f) -> Option<Vec2f> {
@@ -163,3 +307,114 @@ impl Ground {
         goals
     }
 }
+
+// Whether the predicate holds for all the tiles that the straight line between the two positions
+// passes through, stopping at the first one it does not hold for. The tiles are visited in order
+// by stepping from tile border to tile border along the line (DDA traversal).
+pub fn all_tiles_on_line(
+    from: &Vec2f,
+    to: &Vec2f,
+    mut predicate: impl FnMut(&Vec2i) -> bool,
+) -> bool {
+    let mut tile = from.as_vec2i();
+    let end = to.as_vec2i();
+    let delta = to.clone() - from.clone();
+    let step_x = if delta.x > 0.0 { 1 } else { -1 };
+    let step_y = if delta.y > 0.0 { 1 } else { -1 };
+
+    // Share of the line between crossing two tile borders, and to the next border
+    let t_delta_x = (1.0 / delta.x).abs();
+    let t_delta_y = (1.0 / delta.y).abs();
+    let mut t_max_x = if delta.x > 0.0 {
+        ((tile.x + 1) as f32 - from.x) / delta.x
+    } else if delta.x < 0.0 {
+        (tile.x as f32 - from.x) / delta.x
+    } else {
+        f32::INFINITY
+    };
+    let mut t_max_y = if delta.y > 0.0 {
+        ((tile.y + 1) as f32 - from.y) / delta.y
+    } else if delta.y < 0.0 {
+        (tile.y as f32 - from.y) / delta.y
+    } else {
+        f32::INFINITY
+    };
+
+    // Through the corner of a tile both are crossed at once, so that the line of sight is the
+    // same in both directions
+    let mut steps_left = (end.x - tile.x).abs() + (end.y - tile.y).abs();
+    if !predicate(&tile) {
+        return false;
+    }
+    while tile != end && steps_left > 0 {
+        steps_left -= 1;
+        let t_max = t_max_x.min(t_max_y);
+        if t_max_x == t_max {
+            tile.x += step_x;
+            t_max_x += t_delta_x;
+        }
+        if t_max_y == t_max {
+            tile.y += step_y;
+            t_max_y += t_delta_y;
+        }
+        if !predicate(&tile) {
+            return false;
+        }
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::rng::new_rng;
+
+    fn empty_ground() -> Ground {
+        let mut ground = Ground::new(&mut new_rng(0));
+        for y in 0..ground.get_height() {
+            for x in 0..ground.get_width() {
+                ground.set_at(x, y, GroundType::Empty);
+            }
+        }
+        ground
+    }
+
+    #[test]
+    fn line_of_sight_is_symmetric() {
+        let ground = Ground::new(&mut new_rng(3));
+        let mut rng = new_rng(4);
+        let mut random_position = || {
+            Vec2f::new(
+                rng.gen_range(1.0..ground.get_width() as f32 - 1.0),
+                rng.gen_range(1.0..ground.get_height() as f32 - 1.0),
+            )
+        };
+        let mut blocked = 0;
+        for _ in 0..1000 {
+            let from = random_position();
+            let to = random_position();
+            let sight = ground.has_line_of_sight(&from, &to);
+            assert_eq!(sight, ground.has_line_of_sight(&to, &from));
+            if !sight {
+                blocked += 1;
+            }
+        }
+        assert!(blocked > 0);
+    }
+
+    #[test]
+    fn wall_blocks_line_of_sight() {
+        let mut ground = empty_ground();
+        let from = Vec2f::new(10.5, 10.5);
+        let to = Vec2f::new(15.5, 10.5);
+        assert!(ground.has_line_of_sight(&from, &to));
+
+        ground.set_at(12, 10, GroundType::Wall);
+        assert!(!ground.has_line_of_sight(&from, &to));
+        assert!(!ground.has_line_of_sight(&to, &from));
+        // A wall next to the line does not block it
+        assert!(ground.has_line_of_sight(&Vec2f::new(10.5, 11.5), &Vec2f::new(15.5, 11.5)));
+        // The wall itself can be seen
+        assert!(ground.has_line_of_sight(&from, &Vec2f::new(12.5, 10.5)));
+    }
+}
@@ -0,0 +1,127 @@
+use serde::{Deserialize, Serialize};
+
+// Refers to an entity or a building. The slot index of a removed object is reused, but with a
+// new generation, so handles to the removed object stop resolving instead of pointing to
+// whatever took its place.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Handle {
+    index: u32,
+    generation: u32,
+}
+
+impl Handle {
+    pub fn get_index(&self) -> usize {
+        self.index as usize
+    }
+}
+
+// Hands out handles and maps them to values in constant time.
+// Only the slot bookkeeping is saved, the values are put back with restore after loading.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(bound = "", from = "HandleSlots")]
+pub struct HandleStore<T> {
+    generations: Vec<u32>,
+    free_indices: Vec<u32>,
+    #[serde(skip)]
+    values: Vec<Option<T>>,
+}
+
+// The saved part of a HandleStore
+#[derive(Deserialize)]
+struct HandleSlots {
+    generations: Vec<u32>,
+    free_indices: Vec<u32>,
+}
+
+// Every slot gets an empty value, including the free ones that restore is never called on
+impl<T> From<HandleSlots> for HandleStore<T> {
+    fn from(slots: HandleSlots) -> Self {
+        let mut values = Vec::new();
+        values.resize_with(slots.generations.len(), || None);
+        HandleStore {
+            generations: slots.generations,
+            free_indices: slots.free_indices,
+            values,
+        }
+    }
+}
+
+impl<T> Default for HandleStore<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> HandleStore<T> {
+    pub fn new() -> HandleStore<T> {
+        HandleStore {
+            generations: Vec::new(),
+            free_indices: Vec::new(),
+            values: Vec::new(),
+        }
+    }
+
+    pub fn insert(&mut self, value: T) -> Handle {
+        let index = match self.free_indices.pop() {
+            Some(index) => index,
+            None => {
+                self.generations.push(0);
+                self.values.push(None);
+                self.generations.len() as u32 - 1
+            }
+        };
+        self.values[index as usize] = Some(value);
+        Handle {
+            index,
+            generation: self.generations[index as usize],
+        }
+    }
+
+    pub fn get(&self, handle: Handle) -> Option<&T> {
+        if self.generations.get(handle.get_index()) != Some(&handle.generation) {
+            return None;
+        }
+        self.values[handle.get_index()].as_ref()
+    }
+
+    pub fn remove(&mut self, handle: Handle) -> Option<T> {
+        self.get(handle)?;
+        self.generations[handle.get_index()] += 1;
+        self.free_indices.push(handle.index);
+        self.values[handle.get_index()].take()
+    }
+
+    // Put back a value that was saved together with its handle
+    pub fn restore(&mut self, handle: Handle, value: T) {
+        if self.generations.get(handle.get_index()) != Some(&handle.generation) {
+            println!("Restoring a stale handle {:?}", handle);
+            return;
+        }
+        self.values[handle.get_index()] = Some(value);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn insert_after_loading_reuses_free_slot() {
+        let mut store: HandleStore<i32> = HandleStore::new();
+        let first = store.insert(1);
+        let second = store.insert(2);
+        store.remove(first);
+
+        let saved = ron::to_string(&store).unwrap();
+        let mut loaded: HandleStore<i32> = ron::from_str(&saved).unwrap();
+
+        // Nothing was restored yet, like after loading a save without live buildings
+        let third = loaded.insert(3);
+        loaded.restore(second, 2);
+        assert_eq!(third.get_index(), first.get_index());
+        assert_eq!(loaded.get(first), None);
+        assert_eq!(loaded.get(second), Some(&2));
+        assert_eq!(loaded.get(third), Some(&3));
+        assert_eq!(loaded.remove(third), Some(3));
+    }
+}
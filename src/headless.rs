@@ -0,0 +1,9 @@
+use crate::game::Game;
+
+// Drives the simulation without a window so it can run on machines without a display
+// (balance experiments, CI)
+pub fn run(game: &mut Game, ticks: u32) {
+    for _ in 0..ticks {
+        game.update();
+    }
+}
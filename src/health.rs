@@ -1,3 +1,6 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize)]
 pub struct Health {
     health: i32,
     max_health: i32,
@@ -15,6 +18,24 @@ impl Health {
         Health { health, max_health }
     }
 
+    pub fn get_health(&self) -> i32 {
+        self.health
+    }
+
+    pub fn get_max_health(&self) -> i32 {
+        self.max_health
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.health >= self.max_health
+    }
+
+    // Both the maximum and the current health go up, e.g. while a building is constructed
+    pub fn increase_max_health(&mut self, amount: i32) {
+        self.max_health += amount;
+        self.health += amount;
+    }
+
     pub fn increment(&mut self, amount: i32) {
         self.health = (self.health + amount).min(self.max_health);
     }
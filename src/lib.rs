@@ -0,0 +1,28 @@
+pub mod building;
+pub mod building_container;
+pub mod building_registry;
+pub mod checksum;
+pub mod command;
+pub mod constants;
+pub mod damage;
+pub mod entity;
+pub mod entity_container;
+pub mod entity_registry;
+pub mod event_handler;
+pub mod game;
+mod game_thing;
+pub mod ground;
+pub mod handle;
+pub mod headless;
+pub mod health;
+pub mod path_finder;
+pub mod projectile;
+pub mod projectile_handler;
+pub mod replay;
+pub mod resources;
+pub mod rng;
+pub mod save;
+pub mod spacial_partition;
+pub mod team;
+pub mod vec;
+pub mod visibility;
@@ -1,14 +1,16 @@
-use rand::Rng;
 use raqote::{DrawOptions, DrawTarget, PathBuilder, SolidSource, Source};
-use rayon::prelude::*;
 use std::time::{Duration, Instant};
 
-use crate::game::Game;
-use crate::vec::Vec2f;
+use rts2::building_registry::{BuildingRegistry, BuildingType, BUILDING_TYPES_PATH};
+use rts2::command::Command;
+use rts2::game::Game;
+use rts2::handle::Handle;
+use rts2::replay::{Replay, ReplayPlayer};
+use rts2::vec::{Vec2f, Vec2i};
 
-use crate::constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
-use crate::entity::EntityType;
-use crate::ground::GroundType;
+use rts2::constants::{SCREEN_HEIGHT, SCREEN_WIDTH};
+use rts2::entity_registry::{EntityRegistry, EntityType, ENTITY_TYPES_PATH};
+use rts2::ground::GroundType;
 use pixels::{Pixels, SurfaceTexture};
 use winit::event_loop::ControlFlow;
 use winit::keyboard::KeyCode;
@@ -19,27 +21,14 @@ use winit::{
 use winit_input_helper::WinitInputHelper;
 use crate::graphics::Graphics;
 
-mod building;
-mod building_container;
 mod camera;
-mod constants;
 mod draw;
-mod entity;
-mod entity_container;
-mod event_handler;
-mod game;
-mod game_thing;
-mod ground;
-mod health;
-mod path_finder;
-mod projectile;
-mod projectile_handler;
-mod resources;
-mod spacial_partition;
-mod team;
-mod vec;
+mod game_renderer;
 mod graphics;
 
+const REPLAY_SEEK_TICKS: u32 = 600;
+const QUICKSAVE_PATH: &str = "quicksave.ron";
+
 async fn run() {
     println!("Hello, world!");
 
@@ -59,7 +48,7 @@ async fn run() {
             .unwrap()
     };
 
-    let mut pixels = {
+    let _pixels = {
         let window_size = window.inner_size();
         let surface_texture = SurfaceTexture::new(window_size.width, window_size.height, &window);
         Pixels::new(SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32, surface_texture).unwrap()
@@ -69,7 +58,40 @@ async fn run() {
 
     let mut dt = DrawTarget::new(SCREEN_WIDTH as i32, SCREEN_HEIGHT as i32);
 
-    let mut game = Game::new();
+    // Usage: rts2 [seed], rts2 --replay <file> or rts2 --load <file>
+    let args: Vec<String> = std::env::args().collect();
+    let mut replay_player: Option<ReplayPlayer> = None;
+    let mut loaded_game: Option<Game> = None;
+    let seed = match args.get(1).map(|arg| arg.as_str()) {
+        Some("--replay") => {
+            let path = args.get(2).expect("--replay needs a file");
+            let replay = Replay::load(path).unwrap();
+            println!("Playing replay {} ({} commands)", path, replay.commands.len());
+            let seed = replay.seed;
+            replay_player = Some(ReplayPlayer::new(replay));
+            seed
+        }
+        Some("--load") => {
+            let path = args.get(2).expect("--load needs a file");
+            let game = Game::load(path).unwrap();
+            println!("Loaded {} at tick {}", path, game.get_tick());
+            let seed = game.get_seed();
+            loaded_game = Some(game);
+            seed
+        }
+        Some(arg) => arg.parse::<u64>().expect("seed must be a positive integer"),
+        None => rand::random::<u64>(),
+    };
+    println!("Seed: {}", seed);
+
+    let mut game = match loaded_game {
+        Some(game) => game,
+        None => Game::new(
+            seed,
+            EntityRegistry::load_or_default(ENTITY_TYPES_PATH),
+            BuildingRegistry::load_or_default(BUILDING_TYPES_PATH),
+        ),
+    };
 
     // let target_fps = 60;
     // let frame_duration = Duration::from_secs(1) / target_fps as u32;
@@ -83,11 +105,13 @@ async fn run() {
     let mut drag_start_pos: Option<Vec2f> = None;
     let mut drag_pos: Option<Vec2f> = None;
 
-    let mut selected_ids: Vec<usize> = Vec::new();
-    let mut selected_building_id: Option<usize> = None;
-
-    let mut draw_time = Duration::from_secs(0);
-    let mut game_update_time = Duration::from_secs(0);
+    let mut selected_ids: Vec<Handle> = Vec::new();
+    let mut selected_building_id: Option<Handle> = None;
+    let mut construct_building_type = BuildingType::TownHall;
+    // Top left of the building being placed, a ghost of it follows the cursor until confirmed
+    let mut placement_preview: Option<Vec2i> = None;
+    // Whose fog of war is shown and which units can be selected, everything is seen with None
+    let mut fog_team: Option<u8> = if replay_player.is_some() { None } else { Some(0) };
 
     let mut graphics = Graphics::new(window).await;
     // graphics.udpate_ui_texture(&dt);
@@ -110,7 +134,7 @@ async fn run() {
             Event::WindowEvent {
                 ref event,
                 window_id,
-            } if window_id == graphics.window().id() => if !graphics.input(event) {
+            } if window_id == graphics.window().id() && !graphics.input(event) => {
                 // match &event {
                 //     Event::WindowEvent {
                 //         window_id,
@@ -118,7 +142,12 @@ async fn run() {
                 //     } if window_id == graphics.window().id() => if !graphics.input(window_event) => {
                 match event {
                     // WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                    WindowEvent::CloseRequested => window_target.exit(),
+                    WindowEvent::CloseRequested => {
+                        if replay_player.is_none() {
+                            save_replay(&game);
+                        }
+                        window_target.exit()
+                    }
                     WindowEvent::RedrawRequested => {
                         let frame_time = now - last_frame_time;
                         last_frame_time = now;
@@ -128,40 +157,56 @@ async fn run() {
                             last_second_fpses.remove(0);
                         }
 
-                        let draw_start = Instant::now();
-
                         dt.clear(SolidSource::from_unpremultiplied_argb(
                             0xff, 0x00, 0x00, 0x00,
                         ));
-                        game.draw(&mut dt, &camera, &selected_ids, &selected_building_id);
-
-                        match (&drag_start_pos, &drag_pos) {
-                            (Some(pos1), Some(pos2)) => {
-                                let mut screen_start_pos = camera.world_to_screen(&pos1);
-                                let mut screen_end_pos = camera.world_to_screen(&pos2);
-
-                                // Draw a rectangle from drag_start_pos to mouse_pos_game
-                                let mut path_builder = PathBuilder::new();
-                                path_builder.move_to(screen_start_pos.x, screen_start_pos.y);
-                                path_builder.line_to(screen_end_pos.x, screen_start_pos.y);
-                                path_builder.line_to(screen_end_pos.x, screen_end_pos.y);
-                                path_builder.line_to(screen_start_pos.x, screen_end_pos.y);
-                                path_builder.close();
-
-                                let path = path_builder.finish();
-
-                                let stroke_style = &mut raqote::StrokeStyle::default();
-                                // stroke_style.width = camera.length_to_pixels(0.1);
-                                dt.fill(
-                                    &path,
-                                    &Source::Solid(SolidSource::from_unpremultiplied_argb(
-                                        0x80, 0xff, 0xff, 0xff,
-                                    )),
-                                    // stroke_style,
-                                    &DrawOptions::new(),
-                                );
-                            }
-                            _ => {}
+                        let drawn_game = match &replay_player {
+                            Some(replay_player) => replay_player.get_game(),
+                            None => &game,
+                        };
+                        game_renderer::draw(
+                            drawn_game,
+                            &mut dt,
+                            &camera,
+                            &selected_ids,
+                            &selected_building_id,
+                            fog_team,
+                        );
+
+                        if let Some(top_left) = &placement_preview {
+                            game_renderer::draw_building_ghost(
+                                drawn_game,
+                                &mut dt,
+                                &camera,
+                                top_left,
+                                construct_building_type,
+                            );
+                        }
+
+                        if let (Some(pos1), Some(pos2)) = (&drag_start_pos, &drag_pos) {
+                            let screen_start_pos = camera.world_to_screen(pos1);
+                            let screen_end_pos = camera.world_to_screen(pos2);
+
+                            // Draw a rectangle from drag_start_pos to mouse_pos_game
+                            let mut path_builder = PathBuilder::new();
+                            path_builder.move_to(screen_start_pos.x, screen_start_pos.y);
+                            path_builder.line_to(screen_end_pos.x, screen_start_pos.y);
+                            path_builder.line_to(screen_end_pos.x, screen_end_pos.y);
+                            path_builder.line_to(screen_start_pos.x, screen_end_pos.y);
+                            path_builder.close();
+
+                            let path = path_builder.finish();
+
+                            // let stroke_style = &mut raqote::StrokeStyle::default();
+                            // stroke_style.width = camera.length_to_pixels(0.1);
+                            dt.fill(
+                                &path,
+                                &Source::Solid(SolidSource::from_unpremultiplied_argb(
+                                    0x80, 0xff, 0xff, 0xff,
+                                )),
+                                // stroke_style,
+                                &DrawOptions::new(),
+                            );
                         }
 
                         // TODO: This thing can not be the most optimal. How can we directly copy the data?
@@ -180,8 +225,6 @@ async fn run() {
                         // Transfer the dt into wgpu::Texture
                         graphics.udpate_ui_texture(&dt);
 
-                        draw_time = Instant::now() - draw_start;
-
                         graphics.update();
                         graphics.window().request_redraw();
                         match graphics.render() {
@@ -227,17 +270,76 @@ async fn run() {
                 }
             }
 
+            // Cycles through the fog of war of each team and seeing everything
+            if input.key_pressed(KeyCode::KeyV) {
+                fog_team = match fog_team {
+                    Some(team_id) if (team_id as usize) + 1 < game.get_teams().len() => {
+                        Some(team_id + 1)
+                    }
+                    Some(_) => None,
+                    None => Some(0),
+                };
+                println!("Fog of war: {:?}", fog_team);
+            }
+
             let scroll_diff = input.scroll_diff();
             if scroll_diff.1 != 0.0 {
                 camera.zoom(1.0 + scroll_diff.1 / 100.0);
             }
 
+            if let Some(replay_player) = &mut replay_player {
+                if input.key_pressed(KeyCode::Space) {
+                    let paused = replay_player.is_paused();
+                    replay_player.set_paused(!paused);
+                }
+                if input.key_pressed(KeyCode::KeyF) {
+                    let speed = replay_player.get_speed();
+                    replay_player.set_speed(if speed >= 8 { 1 } else { speed * 2 });
+                    println!("Replay speed: {}x", replay_player.get_speed());
+                }
+                if input.key_pressed(KeyCode::ArrowRight) {
+                    let tick = replay_player.get_tick();
+                    replay_player.seek(tick + REPLAY_SEEK_TICKS);
+                }
+                if input.key_pressed(KeyCode::ArrowLeft) {
+                    let tick = replay_player.get_tick();
+                    replay_player.seek(tick.saturating_sub(REPLAY_SEEK_TICKS));
+                }
+            }
+
+            if replay_player.is_none() {
+                if input.key_pressed(KeyCode::F5) {
+                    match game.save(QUICKSAVE_PATH) {
+                        Ok(_) => println!("Saved game to {}", QUICKSAVE_PATH),
+                        Err(e) => println!("Could not save game: {}", e),
+                    }
+                }
+                if input.key_pressed(KeyCode::F9) {
+                    match Game::load(QUICKSAVE_PATH) {
+                        Ok(loaded_game) => {
+                            println!("Loaded game from {}", QUICKSAVE_PATH);
+                            game = loaded_game;
+                            selected_ids.clear();
+                            selected_building_id = None;
+                        }
+                        Err(e) => println!("Could not load game: {}", e),
+                    }
+                }
+            }
+
+            // Player commands are ignored while watching a replay
+            let mut commands: Vec<Command> = Vec::new();
+
             if input.key_pressed(KeyCode::KeyH) {
-                game.command_entities_simple(&selected_ids, false, true);
+                commands.push(Command::Hold {
+                    entity_ids: selected_ids.clone(),
+                });
             }
 
             if input.key_pressed(KeyCode::KeyG) {
-                game.command_entities_simple(&selected_ids, true, false);
+                commands.push(Command::Idle {
+                    entity_ids: selected_ids.clone(),
+                });
             }
 
             let cursor_option = input.cursor();
@@ -247,78 +349,205 @@ async fn run() {
                     camera.screen_to_world(&Vec2f::new(cursor.0 / scale, cursor.1 / scale));
 
                 if let Some(building_id) = selected_building_id {
+                    let mut spawn = |name: &str| {
+                        commands.push(Command::Spawn {
+                            building_id,
+                            entity_type: EntityType::new(name),
+                        })
+                    };
                     if input.key_pressed_os(KeyCode::KeyI) {
-                        game.command_building_spawn(building_id, EntityType::Worker);
+                        spawn("worker");
                     }
                     if input.key_pressed_os(KeyCode::KeyO) {
-                        game.command_building_spawn(building_id, EntityType::Ranged);
+                        spawn("ranged");
                     }
                     if input.key_pressed_os(KeyCode::KeyP) {
-                        game.command_building_spawn(building_id, EntityType::Melee);
+                        spawn("melee");
+                    }
+                    if input.key_pressed_os(KeyCode::KeyC) {
+                        spawn("catapult");
+                    }
+                    if input.key_pressed_os(KeyCode::KeyY) {
+                        spawn("ballista");
                     }
                 }
 
-                if input.key_pressed(KeyCode::KeyB) {
-                    game.command_construct_building(&selected_ids, &cursor_game_pos.as_vec2i());
+                // 1-6 choose what to construct and show where it would go. B or left click places
+                // it, Escape or right click cancels.
+                let building_type_keys = [
+                    (KeyCode::Digit1, BuildingType::TownHall),
+                    (KeyCode::Digit2, BuildingType::Barracks),
+                    (KeyCode::Digit3, BuildingType::ArcheryRange),
+                    (KeyCode::Digit4, BuildingType::Farm),
+                    (KeyCode::Digit5, BuildingType::Tower),
+                    (KeyCode::Digit6, BuildingType::Wall),
+                ];
+                for (key, building_type) in building_type_keys {
+                    if input.key_pressed(key) {
+                        construct_building_type = building_type;
+                        placement_preview = Some(cursor_game_pos.as_vec2i());
+                        println!("Constructing: {:?}", construct_building_type);
+                    }
+                }
+                let mut place_building = false;
+                let was_placing = placement_preview.is_some();
+                if was_placing {
+                    placement_preview = Some(cursor_game_pos.as_vec2i());
+                    place_building = input.key_pressed(KeyCode::KeyB) || input.mouse_pressed(0);
+                    if input.key_pressed(KeyCode::Escape) || input.mouse_pressed(1) {
+                        placement_preview = None;
+                    }
+                } else if input.key_pressed(KeyCode::KeyB) {
+                    placement_preview = Some(cursor_game_pos.as_vec2i());
+                }
+
+                if let Some(building_id) = selected_building_id {
+                    if input.key_pressed(KeyCode::Backspace) {
+                        commands.push(Command::CancelSpawn {
+                            building_id,
+                            index: None,
+                        });
+                    }
+                    // Delete cancels a construction site or demolishes a finished building of the
+                    // team that is played
+                    if input.key_pressed(KeyCode::Delete) {
+                        let is_constructed = game
+                            .get_building_container()
+                            .get_building_by_id(building_id)
+                            .filter(|building| Some(building.borrow().get_team()) == fog_team)
+                            .map(|building| building.borrow().is_constructed());
+                        if let (Some(is_constructed), Some(team)) = (is_constructed, fog_team) {
+                            if is_constructed {
+                                commands.push(Command::Demolish { building_id, team });
+                            } else {
+                                commands.push(Command::CancelConstruction { building_id, team });
+                            }
+                        }
+                    }
+                }
+
+                if place_building {
+                    commands.push(Command::ConstructBuilding {
+                        entity_ids: selected_ids.clone(),
+                        top_left: cursor_game_pos.as_vec2i(),
+                        building_type: construct_building_type,
+                    });
+                    placement_preview = None;
                 }
 
                 if input.key_held(KeyCode::KeyJ) {
-                    game.ground.set_at(
-                        cursor_game_pos.x as i32,
-                        cursor_game_pos.y as i32,
-                        GroundType::Wall,
-                    );
+                    commands.push(Command::SetGround {
+                        position: cursor_game_pos.as_vec2i(),
+                        ground_type: GroundType::Wall,
+                    });
                 }
                 if input.key_held(KeyCode::KeyK) {
-                    game.ground.set_at(
-                        cursor_game_pos.x as i32,
-                        cursor_game_pos.y as i32,
-                        GroundType::Empty,
-                    );
+                    commands.push(Command::SetGround {
+                        position: cursor_game_pos.as_vec2i(),
+                        ground_type: GroundType::Empty,
+                    });
                 }
 
-                if input.mouse_pressed(1) || input.key_pressed(KeyCode::KeyR) {
+                // Left clicking or dragging on the minimap moves the camera there, right
+                // clicking it moves the selected units there
+                let minimap_pos = if was_placing {
+                    None
+                } else {
+                    game_renderer::minimap_to_world(
+                        &Vec2f::new(cursor.0 / scale, cursor.1 / scale),
+                    )
+                };
+                if let Some(minimap_pos) = &minimap_pos {
+                    if input.mouse_held(0) && drag_start_pos.is_none() {
+                        camera.set_position(minimap_pos);
+                    }
+                }
+
+                // Clicks while placing a building are only for placing it
+                if (input.mouse_pressed(1) || input.key_pressed(KeyCode::KeyR)) && !was_placing {
                     if let Some(building_id) = selected_building_id {
-                        game.set_spawn_command_position(building_id, &cursor_game_pos);
+                        commands.push(Command::Rally {
+                            building_id,
+                            position: minimap_pos
+                                .clone()
+                                .unwrap_or_else(|| cursor_game_pos.clone()),
+                        });
+                    } else if let Some(minimap_pos) = &minimap_pos {
+                        if input.key_held(KeyCode::KeyE) {
+                            commands.push(Command::AttackMove {
+                                entity_ids: selected_ids.clone(),
+                                position: minimap_pos.clone(),
+                            });
+                        } else {
+                            commands.push(Command::Move {
+                                entity_ids: selected_ids.clone(),
+                                position: minimap_pos.clone(),
+                            });
+                        }
                     } else {
-                        game.command_entities_move(
-                            &selected_ids,
+                        commands.push(game.command_at(
+                            selected_ids.clone(),
                             &cursor_game_pos,
                             input.key_held(KeyCode::KeyE),
-                        );
+                        ));
                     }
                 }
-                if input.mouse_pressed(0) {
-                    drag_start_pos = Some(cursor_game_pos.clone());
-                    drag_pos = Some(cursor_game_pos.clone());
+                // Clicking an item of the spawn queue cancels it instead of starting a selection
+                let clicked_spawn_queue_index = selected_building_id.and_then(|building_id| {
+                    let building = game.get_building_container().get_building_by_id(building_id)?;
+                    let queue_length = building.borrow().get_spawn_queue().len();
+                    game_renderer::spawn_queue_index_at(
+                        &Vec2f::new(cursor.0 / scale, cursor.1 / scale),
+                        queue_length,
+                    )
+                });
+                if input.mouse_pressed(0) && !was_placing {
+                    if let (Some(building_id), Some(index)) =
+                        (selected_building_id, clicked_spawn_queue_index)
+                    {
+                        commands.push(Command::CancelSpawn {
+                            building_id,
+                            index: Some(index),
+                        });
+                    } else if minimap_pos.is_none() {
+                        drag_start_pos = Some(cursor_game_pos.clone());
+                        drag_pos = Some(cursor_game_pos.clone());
+                    }
                 }
                 if input.mouse_held(0) {
                     drag_pos = Some(cursor_game_pos.clone());
                 } else {
-                    match (&drag_start_pos, &drag_pos) {
-                        (Some(p1), Some(p2)) => {
-                            selected_building_id = None;
-
-                            let top_left = Vec2f::new(p1.x.min(p2.x), p1.y.min(p2.y));
-                            let bottom_right = Vec2f::new(p1.x.max(p2.x), p1.y.max(p2.y));
-                            let new_selected_ids =
-                                game.entity_ids_in_bounding_box(&top_left, &bottom_right);
+                    if let (Some(p1), Some(p2)) = (&drag_start_pos, &drag_pos) {
+                        selected_building_id = None;
+
+                        let top_left = Vec2f::new(p1.x.min(p2.x), p1.y.min(p2.y));
+                        let bottom_right = Vec2f::new(p1.x.max(p2.x), p1.y.max(p2.y));
+                        let selectable_game = match &replay_player {
+                            Some(replay_player) => replay_player.get_game(),
+                            None => &game,
+                        };
+                        let new_selected_ids = selectable_game.entity_ids_in_bounding_box(
+                            &top_left,
+                            &bottom_right,
+                            fog_team,
+                        );
 
-                            if input.key_held(KeyCode::ShiftLeft) {
-                                selected_ids.extend(new_selected_ids);
-                            } else {
-                                selected_ids = new_selected_ids;
-                            }
+                        if input.key_held(KeyCode::ShiftLeft) {
+                            selected_ids.extend(new_selected_ids);
+                        } else {
+                            selected_ids = new_selected_ids;
+                        }
 
-                            println!("Selected entities: {:?}", selected_ids);
-                            if selected_ids.len() == 0 {
-                                let building_id = game
-                                    .first_building_id_in_bouding_box(&top_left, &bottom_right);
-                                println!("Selected building: {:?}", building_id);
-                                selected_building_id = building_id;
-                            }
+                        println!("Selected entities: {:?}", selected_ids);
+                        if selected_ids.is_empty() {
+                            let building_id = selectable_game.first_building_id_in_bouding_box(
+                                &top_left,
+                                &bottom_right,
+                                fog_team,
+                            );
+                            println!("Selected building: {:?}", building_id);
+                            selected_building_id = building_id;
                         }
-                        _ => {}
                     }
 
                     drag_start_pos = None;
@@ -326,6 +555,12 @@ async fn run() {
                 }
             }
 
+            if replay_player.is_none() {
+                for command in commands {
+                    game.queue_command(command);
+                }
+            }
+
             let ups_dur = Duration::from_secs_f32(1.0 / 60.0);
 
             let lag = now - update_timer;
@@ -337,11 +572,12 @@ async fn run() {
                 if update_timer + ups_dur <= now {
                     update_timer += ups_dur;
 
-                    let game_update_start = Instant::now();
-                    game.update();
-                    game_update_time = Instant::now() - game_update_start;
+                    match &mut replay_player {
+                        Some(replay_player) => replay_player.update(),
+                        None => game.update(),
+                    }
 
-                    let mut update_time = now - last_update_time;
+                    let update_time = now - last_update_time;
                     last_update_time = now;
                     let current_ups = 1.0 / update_time.as_secs_f32();
                     last_seconds_upses.push(current_ups);
@@ -355,6 +591,19 @@ async fn run() {
         .unwrap();
 }
 
+// Every game is written to a replay file when the window is closed
+fn save_replay(game: &Game) {
+    let path = format!("replays/replay_{}.ron", game.get_seed());
+    if let Err(e) = std::fs::create_dir_all("replays") {
+        println!("Could not create replays directory: {}", e);
+        return;
+    }
+    match Replay::from_game(game).save(&path) {
+        Ok(_) => println!("Saved replay to {}", path),
+        Err(e) => println!("Could not save replay: {}", e),
+    }
+}
+
 fn main() {
     pollster::block_on(run());
 }
@@ -1,5 +1,6 @@
 use crate::ground::Ground;
 use crate::vec::{Vec2f, Vec2i};
+use serde::{Deserialize, Serialize};
 use std::cell::RefCell;
 use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap, HashSet};
@@ -7,12 +8,13 @@ use std::rc::Rc;
 
 type PathItem = (i32, i32);
 
-#[derive(Clone)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum PathGoal {
     Point { pos: Vec2f },
     Rect { pos: Vec2i, size: Vec2i },
 }
 
+#[derive(Serialize, Deserialize)]
 pub struct Path {
     pub position_datas: HashMap<PathItem, Vec2f>,
     pub goal: PathGoal,
@@ -38,6 +40,12 @@ pub fn distance_to_big_block(entity_pos: &Vec2f, pos: &Vec2i, size: &Vec2i) -> f
     (x_diff * x_diff + y_diff * y_diff).sqrt()
 }
 
+// HashSet iteration order is random, so always pick the start positions in a fixed order.
+// Otherwise the same search could produce a different path on each run.
+fn first_start_position(start_positions: &HashSet<Vec2i>) -> Option<&Vec2i> {
+    start_positions.iter().min_by_key(|pos| (pos.x, pos.y))
+}
+
 fn get_dirs(direction: &Vec2f) -> (i32, i32) {
     let dir_y = if direction.y > 0.0 {
         1
@@ -69,7 +77,7 @@ fn two_directions_converge(
 
     let thing2 = (line_1_end.clone() - line_2_end.clone()).length();
 
-    return thing1 > thing2;
+    thing1 > thing2
 }
 
 fn get_two_lines_intersection(
@@ -123,10 +131,7 @@ impl Path {
 
     pub fn get_direction(&self, position: &Vec2i) -> Option<Vec2f> {
         let position = (position.x, position.y);
-        match self.position_datas.get(&position) {
-            Some(direction) => Some(direction.clone()),
-            None => None,
-        }
+        self.position_datas.get(&position).cloned()
     }
 
     pub fn distance_to_goal(&self, goal: &Vec2f) -> f32 {
@@ -161,57 +166,56 @@ impl Path {
                     println!("I do not think this should be possible :/");
                 }
                 (1, 1) | (1, -1) | (-1, 1) | (-1, -1) => {
-                    let pos_1 = (path_item.0 + dir_x, path_item.1 + 0);
+                    let pos_1 = (path_item.0 + dir_x, path_item.1);
                     let other_1 = self.position_datas.get(&pos_1);
-                    let pos_2 = (path_item.0 + 0, path_item.1 + dir_y);
+                    let pos_2 = (path_item.0, path_item.1 + dir_y);
                     let other_2 = self.position_datas.get(&pos_2);
                     let pos_3 = (path_item.0 + dir_x, path_item.1 + dir_y);
                     let other_3 = self.position_datas.get(&pos_3);
 
-                    match (other_1, other_2, other_3) {
-                        (Some(other_1), Some(other_2), Some(other_3)) => {
-                            if two_directions_converge(
+                    if let (Some(other_1), Some(other_2), Some(other_3)) =
+                        (other_1, other_2, other_3)
+                    {
+                        if two_directions_converge(
+                            &Vec2f::new(pos_1.0 as f32, pos_1.1 as f32),
+                            other_1,
+                            &Vec2f::new(pos_2.0 as f32, pos_2.1 as f32),
+                            other_2,
+                        ) {
+                            new_direction = Some(get_two_lines_intersection(
+                                &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
                                 &Vec2f::new(pos_1.0 as f32, pos_1.1 as f32),
                                 other_1,
                                 &Vec2f::new(pos_2.0 as f32, pos_2.1 as f32),
                                 other_2,
-                            ) {
-                                new_direction = Some(get_two_lines_intersection(
-                                    &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
-                                    &Vec2f::new(pos_1.0 as f32, pos_1.1 as f32),
-                                    other_1,
-                                    &Vec2f::new(pos_2.0 as f32, pos_2.1 as f32),
-                                    other_2,
-                                ));
-                            } else if two_directions_converge(
+                            ));
+                        } else if two_directions_converge(
+                            &Vec2f::new(pos_3.0 as f32, pos_3.1 as f32),
+                            other_3,
+                            &Vec2f::new(pos_2.0 as f32, pos_2.1 as f32),
+                            other_2,
+                        ) {
+                            new_direction = Some(get_two_lines_intersection(
+                                &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
                                 &Vec2f::new(pos_3.0 as f32, pos_3.1 as f32),
                                 other_3,
                                 &Vec2f::new(pos_2.0 as f32, pos_2.1 as f32),
                                 other_2,
-                            ) {
-                                new_direction = Some(get_two_lines_intersection(
-                                    &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
-                                    &Vec2f::new(pos_3.0 as f32, pos_3.1 as f32),
-                                    other_3,
-                                    &Vec2f::new(pos_2.0 as f32, pos_2.1 as f32),
-                                    other_2,
-                                ));
-                            } else if two_directions_converge(
+                            ));
+                        } else if two_directions_converge(
+                            &Vec2f::new(pos_1.0 as f32, pos_1.1 as f32),
+                            other_1,
+                            &Vec2f::new(pos_3.0 as f32, pos_3.1 as f32),
+                            other_3,
+                        ) {
+                            new_direction = Some(get_two_lines_intersection(
+                                &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
                                 &Vec2f::new(pos_1.0 as f32, pos_1.1 as f32),
                                 other_1,
                                 &Vec2f::new(pos_3.0 as f32, pos_3.1 as f32),
                                 other_3,
-                            ) {
-                                new_direction = Some(get_two_lines_intersection(
-                                    &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
-                                    &Vec2f::new(pos_1.0 as f32, pos_1.1 as f32),
-                                    other_1,
-                                    &Vec2f::new(pos_3.0 as f32, pos_3.1 as f32),
-                                    other_3,
-                                ));
-                            }
+                            ));
                         }
-                        _ => {}
                     }
                 }
                 (0, 1) | (1, 0) | (-1, 0) | (0, -1) => {
@@ -225,12 +229,12 @@ impl Path {
                             // other_dir_x is meaningful
                             let other_2 = self
                                 .position_datas
-                                .get(&(path_item.0 + other_dir_x, path_item.1 + 0));
+                                .get(&(path_item.0 + other_dir_x, path_item.1));
                             let other_3 = self
                                 .position_datas
                                 .get(&(path_item.0 + other_dir_x, path_item.1 + dir_y));
                             match (other_2, other_3) {
-                                (Some(other_2), Some(other_3)) => {
+                                (Some(other_2), Some(_other_3))
                                     if two_directions_converge(
                                         &Vec2f::new(
                                             (path_item.0 + dir_x) as f32,
@@ -239,24 +243,24 @@ impl Path {
                                         other,
                                         &Vec2f::new(
                                             (path_item.0 + other_dir_x) as f32,
-                                            (path_item.1 + 0) as f32,
+                                            path_item.1 as f32,
+                                        ),
+                                        other_2,
+                                    ) =>
+                                {
+                                    new_direction = Some(get_two_lines_intersection(
+                                        &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
+                                        &Vec2f::new(
+                                            (path_item.0 + dir_x) as f32,
+                                            (path_item.1 + dir_y) as f32,
+                                        ),
+                                        other,
+                                        &Vec2f::new(
+                                            (path_item.0 + other_dir_x) as f32,
+                                            path_item.1 as f32,
                                         ),
                                         other_2,
-                                    ) {
-                                        new_direction = Some(get_two_lines_intersection(
-                                            &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
-                                            &Vec2f::new(
-                                                (path_item.0 + dir_x) as f32,
-                                                (path_item.1 + dir_y) as f32,
-                                            ),
-                                            other,
-                                            &Vec2f::new(
-                                                (path_item.0 + other_dir_x) as f32,
-                                                (path_item.1 + 0) as f32,
-                                            ),
-                                            other_2,
-                                        ));
-                                    }
+                                    ));
                                 }
                                 _ => {}
                             }
@@ -264,12 +268,12 @@ impl Path {
                             // other_dir_y is meaningful
                             let other_2 = self
                                 .position_datas
-                                .get(&(path_item.0 + 0, path_item.1 + other_dir_y));
+                                .get(&(path_item.0, path_item.1 + other_dir_y));
                             let other_3 = self
                                 .position_datas
                                 .get(&(path_item.0 + dir_x, path_item.1 + other_dir_y));
                             match (other_2, other_3) {
-                                (Some(other_2), Some(other_3)) => {
+                                (Some(other_2), Some(_other_3))
                                     if two_directions_converge(
                                         &Vec2f::new(
                                             (path_item.0 + dir_x) as f32,
@@ -277,25 +281,25 @@ impl Path {
                                         ),
                                         other,
                                         &Vec2f::new(
-                                            (path_item.0 + 0) as f32,
+                                            path_item.0 as f32,
                                             (path_item.1 + other_dir_y) as f32,
                                         ),
                                         other_2,
-                                    ) {
-                                        new_direction = Some(get_two_lines_intersection(
-                                            &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
-                                            &Vec2f::new(
-                                                (path_item.0 + dir_x) as f32,
-                                                (path_item.1 + dir_y) as f32,
-                                            ),
-                                            other,
-                                            &Vec2f::new(
-                                                (path_item.0 + 0) as f32,
-                                                (path_item.1 + other_dir_y) as f32,
-                                            ),
-                                            other_2,
-                                        ));
-                                    }
+                                    ) =>
+                                {
+                                    new_direction = Some(get_two_lines_intersection(
+                                        &Vec2f::new(path_item.0 as f32, path_item.1 as f32),
+                                        &Vec2f::new(
+                                            (path_item.0 + dir_x) as f32,
+                                            (path_item.1 + dir_y) as f32,
+                                        ),
+                                        other,
+                                        &Vec2f::new(
+                                            path_item.0 as f32,
+                                            (path_item.1 + other_dir_y) as f32,
+                                        ),
+                                        other_2,
+                                    ));
                                 }
                                 _ => {}
                             }
@@ -373,6 +377,12 @@ impl PartialOrd for WPathItem {
     }
 }
 
+impl Default for PathFinder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl PathFinder {
     pub fn new() -> PathFinder {
         PathFinder {
@@ -392,7 +402,7 @@ impl PathFinder {
         let mut path_items: HashMap<PathItem, Vec2f> = HashMap::new();
 
         let mut unfound_start_positions = start_positions.clone();
-        let mut current_start_position = match unfound_start_positions.iter().next() {
+        let mut current_start_position = match first_start_position(&unfound_start_positions) {
             Some(pos) => pos.clone(),
             None => return None,
         };
@@ -491,12 +501,13 @@ impl PathFinder {
                     if current_start_position.x == new_position.0
                         && current_start_position.y == new_position.1
                     {
-                        current_start_position = match unfound_start_positions.iter().next() {
-                            Some(pos) => pos.clone(),
-                            None => {
-                                panic!("No more start positions left, this should not happen")
-                            }
-                        };
+                        current_start_position =
+                            match first_start_position(&unfound_start_positions) {
+                                Some(pos) => pos.clone(),
+                                None => {
+                                    panic!("No more start positions left, this should not happen")
+                                }
+                            };
 
                         // Do the re weighting of all the WPathItems
                         unhandled_positions = unhandled_positions
@@ -1,27 +1,56 @@
-use crate::vec::Vec2f;
+use crate::building_container::BuildingContainer;
+use crate::constants::PROJECTILE_RADIUS;
+use crate::damage::DamageType;
+use crate::entity::AttackTarget;
+use crate::entity_registry::ProjectileKind;
+use crate::ground::Ground;
+use crate::vec::{Vec2f, Vec2i};
+use serde::{Deserialize, Serialize};
 
+// What a projectile does to what it hits
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ProjectilePayload {
+    pub damage: i32,
+    pub damage_type: DamageType,
+    pub splash_radius: f32,  // 0.0 hits a single target
+    pub friendly_fire: bool, // The splash also damages the own team
+}
+
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Projectile {
     position: Vec2f,
+    previous_position: Vec2f, // Where it was at the start of the tick, for collisions on the way
     goal: Option<Vec2f>,
-    damage: i32,
+    kind: ProjectileKind,
+    payload: ProjectilePayload,
     speed: f32,
     team: u8,
+    homing_target: Option<AttackTarget>, // The goal follows this target
+    blocked_at: Option<Vec2i>,           // The tile that stopped it
+    hit_targets: Vec<AttackTarget>,      // Piercing projectiles hit every target only once
 }
 
 impl Projectile {
     pub fn new(
         position: Vec2f,
         goal: Option<Vec2f>,
-        damage: i32,
+        kind: ProjectileKind,
+        payload: ProjectilePayload,
         speed: f32,
         team: u8,
+        homing_target: Option<AttackTarget>,
     ) -> Projectile {
         Projectile {
+            previous_position: position.clone(),
             position,
             goal,
-            damage,
+            kind,
+            payload,
             speed,
             team,
+            homing_target,
+            blocked_at: None,
+            hit_targets: Vec::new(),
         }
     }
 
@@ -33,29 +62,154 @@ impl Projectile {
         self.position.clone()
     }
 
-    pub fn get_damage(&self) -> i32 {
-        self.damage
+    pub fn get_kind(&self) -> ProjectileKind {
+        self.kind
     }
 
-    pub fn progress(&mut self) {
-        if let Some(goal) = &self.goal {
-            let direction = goal.clone() - self.position.clone();
-            let distance = direction.length();
-            let direction = direction.normalized();
-            let distance_to_travel = self.speed;
-            if distance < distance_to_travel {
-                self.position = goal.clone();
-                self.goal = None
-            } else {
-                self.position += direction * distance_to_travel;
-            }
+    pub fn get_payload(&self) -> &ProjectilePayload {
+        &self.payload
+    }
+
+    pub fn get_homing_target(&self) -> Option<AttackTarget> {
+        self.homing_target
+    }
+
+    pub fn get_blocked_at(&self) -> Option<Vec2i> {
+        self.blocked_at.clone()
+    }
+
+    // Bolts damage everything on their way instead of stopping at the first target
+    pub fn is_piercing(&self) -> bool {
+        self.kind == ProjectileKind::Bolt
+    }
+
+    // Boulders fly over everything and only hit where they land
+    pub fn is_lobbed(&self) -> bool {
+        self.kind == ProjectileKind::Boulder
+    }
+
+    // Arrows and bolts that reach their goal without hitting anything have missed
+    pub fn hits_on_the_way(&self) -> bool {
+        matches!(self.kind, ProjectileKind::Arrow | ProjectileKind::Bolt)
+    }
+
+    pub fn has_hit(&self, target: &AttackTarget) -> bool {
+        self.hit_targets.contains(target)
+    }
+
+    pub fn add_hit(&mut self, target: AttackTarget) {
+        self.hit_targets.push(target);
+    }
+
+    // Whether a unit at `position` was touched during the last progress
+    pub fn passed_through(&self, position: &Vec2f, radius: f32) -> bool {
+        position.distance_to_segment(&self.previous_position, &self.position)
+            <= radius + PROJECTILE_RADIUS
+    }
+
+    // How far along the last progress `position` was passed, to find what was hit first
+    pub fn distance_travelled_to(&self, position: &Vec2f) -> f32 {
+        (position.clone() - self.previous_position.clone()).length()
+    }
+
+    // Only moves the goal while still flying
+    pub fn set_goal(&mut self, goal: Vec2f) {
+        if self.goal.is_some() {
+            self.goal = Some(goal);
+        }
+    }
+
+    pub fn stop(&mut self) {
+        self.goal = None;
+    }
+
+    // Moves towards the goal. Unless lobbed it stops at the first blocked tile that is not a
+    // building of its own team, so that towers can shoot out of themselves.
+    pub fn progress(&mut self, ground: &Ground, building_container: &BuildingContainer) {
+        self.previous_position = self.position.clone();
+        let goal = match &self.goal {
+            Some(goal) => goal.clone(),
+            None => return,
+        };
+
+        let direction = goal.clone() - self.position.clone();
+        let distance = direction.length();
+        let direction = direction.normalized();
+        let distance_to_travel = self.speed;
+        if distance < distance_to_travel {
+            self.position = goal;
+            self.goal = None
+        } else {
+            self.position += direction * distance_to_travel;
+        }
+
+        if self.is_lobbed() {
+            return;
+        }
+        let tile = self.position.as_vec2i();
+        if ground.blocked_at(tile.x, tile.y)
+            && building_container
+                .get_building_on_tile(&tile, Some(self.team), None)
+                .is_none()
+        {
+            self.blocked_at = Some(tile);
+            self.goal = None;
         }
     }
 
     pub fn ready_to_impact(&self) -> bool {
-        match self.goal {
-            Some(_) => false,
-            None => true,
+        self.goal.is_none()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::building::Building;
+    use crate::building_registry::{BuildingRegistry, BuildingType};
+    use crate::ground::GroundType;
+    use crate::rng::new_rng;
+
+    fn empty_ground() -> Ground {
+        let mut ground = Ground::new(&mut new_rng(0));
+        for y in 0..ground.get_height() {
+            for x in 0..ground.get_width() {
+                ground.set_at(x, y, GroundType::Empty);
+            }
+        }
+        ground
+    }
+
+    #[test]
+    fn wall_next_to_own_building_stops_arrow() {
+        let mut ground = empty_ground();
+        let mut building_container = BuildingContainer::new();
+        let registry = BuildingRegistry::default();
+        let definition = registry.get(BuildingType::Barracks).unwrap().clone();
+        let building = Building::new(Vec2i::new(10, 10), 0, definition, true);
+        let wall = Vec2i::new(10 + building.get_width(), 10);
+        building_container.add_building(building, &mut ground);
+        ground.set_at(wall.x, wall.y, GroundType::Wall);
+
+        // Shot from inside the building through the wall tile to its right
+        let payload = ProjectilePayload {
+            damage: 1,
+            damage_type: DamageType::Pierce,
+            splash_radius: 0.0,
+            friendly_fire: false,
+        };
+        let mut projectile = Projectile::new(
+            Vec2f::new(10.5, 10.5),
+            Some(Vec2f::new(wall.x as f32 + 5.5, 10.5)),
+            ProjectileKind::Arrow,
+            payload,
+            0.25,
+            0,
+            None,
+        );
+        while !projectile.ready_to_impact() {
+            projectile.progress(&ground, &building_container);
         }
+        assert_eq!(projectile.get_blocked_at(), Some(wall));
     }
 }
@@ -1,11 +1,23 @@
-use crate::projectile::Projectile;
+use crate::building_container::BuildingContainer;
+use crate::entity::AttackTarget;
+use crate::entity_registry::ProjectileKind;
+use crate::ground::Ground;
+use crate::projectile::{Projectile, ProjectilePayload};
 use crate::vec::Vec2f;
+use serde::{Deserialize, Serialize};
 use std::slice::Iter;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct ProjectileHandler {
     projectiles: Vec<Projectile>,
 }
 
+impl Default for ProjectileHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl ProjectileHandler {
     pub fn new() -> ProjectileHandler {
         ProjectileHandler {
@@ -13,17 +25,21 @@ impl ProjectileHandler {
         }
     }
 
-    pub fn iter(&self) -> Iter<Projectile> {
+    pub fn iter(&self) -> Iter<'_, Projectile> {
         self.projectiles.iter()
     }
 
-    pub fn iter_mut(&mut self) -> std::slice::IterMut<Projectile> {
+    pub fn iter_mut(&mut self) -> std::slice::IterMut<'_, Projectile> {
         self.projectiles.iter_mut()
     }
 
-    pub fn progress_projectiles(&mut self) {
+    pub fn progress_projectiles(
+        &mut self,
+        ground: &Ground,
+        building_container: &BuildingContainer,
+    ) {
         for projectile in self.projectiles.iter_mut() {
-            projectile.progress();
+            projectile.progress(ground, building_container);
         }
     }
 
@@ -42,13 +58,42 @@ impl ProjectileHandler {
             .retain(|projectile| !projectile.ready_to_impact())
     }
 
-    pub fn add_meelee_projectile(&mut self, position: Vec2f, team: u8) {
-        let projectile = Projectile::new(position, None, 9, 0.0, team);
+    pub fn add_meelee_projectile(&mut self, position: Vec2f, payload: ProjectilePayload, team: u8) {
+        let projectile = Projectile::new(
+            position,
+            None,
+            ProjectileKind::Melee,
+            payload,
+            0.0,
+            team,
+            None,
+        );
         self.projectiles.push(projectile);
     }
 
-    pub fn add_ranged_projectile(&mut self, position: Vec2f, goal: Vec2f, team: u8) {
-        let projectile = Projectile::new(position, Some(goal), 9, 0.1, team);
+    pub fn add_ranged_projectile(
+        &mut self,
+        position: Vec2f,
+        goal: Vec2f,
+        kind: ProjectileKind,
+        payload: ProjectilePayload,
+        team: u8,
+        homing_target: Option<AttackTarget>,
+    ) {
+        let speed = match kind {
+            ProjectileKind::Boulder => 0.06,
+            ProjectileKind::Bolt => 0.3,
+            _ => 0.2,
+        };
+        let projectile = Projectile::new(
+            position,
+            Some(goal),
+            kind,
+            payload,
+            speed,
+            team,
+            homing_target,
+        );
         self.projectiles.push(projectile);
     }
 }
@@ -0,0 +1,131 @@
+use crate::building_registry::BuildingRegistry;
+use crate::command::RecordedCommand;
+use crate::entity_registry::EntityRegistry;
+use crate::game::Game;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+
+// Everything needed to reproduce a game: the seed, the unit and building definitions and the
+// commands the players gave
+#[derive(Clone, Serialize, Deserialize)]
+pub struct Replay {
+    pub seed: u64,
+    pub entity_registry: EntityRegistry,
+    pub building_registry: BuildingRegistry,
+    pub commands: Vec<RecordedCommand>,
+}
+
+impl Replay {
+    pub fn from_game(game: &Game) -> Replay {
+        Replay {
+            seed: game.get_seed(),
+            entity_registry: game.get_entity_registry().clone(),
+            building_registry: game.get_building_registry().clone(),
+            commands: game.get_command_log().to_vec(),
+        }
+    }
+
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &str) -> io::Result<Replay> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+// Re-runs a replay from its seed, feeding in the recorded commands on the right ticks
+pub struct ReplayPlayer {
+    replay: Replay,
+    game: Game,
+    next_command: usize,
+    paused: bool,
+    speed: u32, // Updates per step
+}
+
+impl ReplayPlayer {
+    pub fn new(replay: Replay) -> ReplayPlayer {
+        let game = Game::new(
+            replay.seed,
+            replay.entity_registry.clone(),
+            replay.building_registry.clone(),
+        );
+        ReplayPlayer {
+            replay,
+            game,
+            next_command: 0,
+            paused: false,
+            speed: 1,
+        }
+    }
+
+    pub fn get_game(&self) -> &Game {
+        &self.game
+    }
+
+    pub fn get_tick(&self) -> u32 {
+        self.game.get_tick()
+    }
+
+    // Tick of the last recorded command
+    pub fn get_last_command_tick(&self) -> u32 {
+        self.replay.commands.last().map_or(0, |c| c.tick)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn set_paused(&mut self, paused: bool) {
+        self.paused = paused;
+    }
+
+    pub fn get_speed(&self) -> u32 {
+        self.speed
+    }
+
+    pub fn set_speed(&mut self, speed: u32) {
+        self.speed = speed.max(1);
+    }
+
+    // Called once per frame update, does nothing while paused
+    pub fn update(&mut self) {
+        if self.paused {
+            return;
+        }
+        for _ in 0..self.speed {
+            self.step();
+        }
+    }
+
+    // Advance exactly one tick
+    pub fn step(&mut self) {
+        while let Some(recorded) = self.replay.commands.get(self.next_command) {
+            if recorded.tick > self.game.get_tick() {
+                break;
+            }
+            self.game.queue_command(recorded.command.clone());
+            self.next_command += 1;
+        }
+        self.game.update();
+    }
+
+    // The simulation can only run forward, so seeking backwards restarts from the seed
+    pub fn seek(&mut self, tick: u32) {
+        if tick < self.game.get_tick() {
+            self.game = Game::new(
+                self.replay.seed,
+                self.replay.entity_registry.clone(),
+                self.replay.building_registry.clone(),
+            );
+            self.next_command = 0;
+        }
+        while self.game.get_tick() < tick {
+            self.step();
+        }
+    }
+}
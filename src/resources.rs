@@ -1,25 +1,135 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::ops;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResourceType {
+    Gold,
+    Wood,
+    Stone,
+}
+
+// Missing kinds default to 0 so that e.g. a cost can be written as (wood: 50)
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
 pub struct Resources {
     pub gold: i32,
+    pub wood: i32,
+    pub stone: i32,
 }
 
 impl Resources {
     pub fn new_empty() -> Resources {
-        Resources { gold: 0 }
+        Resources::default()
+    }
+
+    pub fn new(gold: i32, wood: i32, stone: i32) -> Resources {
+        Resources { gold, wood, stone }
+    }
+
+    // Only one kind of resource, e.g. what a worker carries
+    pub fn new_single(resource_type: ResourceType, amount: i32) -> Resources {
+        let mut resources = Resources::new_empty();
+        *resources.get_mut(resource_type) += amount;
+        resources
     }
 
-    pub fn new(gold: i32) -> Resources {
-        Resources { gold }
+    pub fn get(&self, resource_type: ResourceType) -> i32 {
+        match resource_type {
+            ResourceType::Gold => self.gold,
+            ResourceType::Wood => self.wood,
+            ResourceType::Stone => self.stone,
+        }
+    }
+
+    pub fn get_mut(&mut self, resource_type: ResourceType) -> &mut i32 {
+        match resource_type {
+            ResourceType::Gold => &mut self.gold,
+            ResourceType::Wood => &mut self.wood,
+            ResourceType::Stone => &mut self.stone,
+        }
     }
 
     pub fn can_afford(&self, other: &Resources) -> bool {
-        self.gold >= other.gold
+        self >= other
     }
 
     pub fn decrement(&mut self, cost: &Resources) {
-        self.gold -= cost.gold;
+        *self = &*self - cost;
     }
 
     pub fn increment(&mut self, amount: &Resources) {
-        self.gold += amount.gold;
+        *self = &*self + amount;
+    }
+}
+
+impl<'b> ops::Add<&'b Resources> for &Resources {
+    type Output = Resources;
+
+    fn add(self, other: &'b Resources) -> Resources {
+        Resources::new(
+            self.gold + other.gold,
+            self.wood + other.wood,
+            self.stone + other.stone,
+        )
+    }
+}
+
+impl<'b> ops::Sub<&'b Resources> for &Resources {
+    type Output = Resources;
+
+    fn sub(self, other: &'b Resources) -> Resources {
+        Resources::new(
+            self.gold - other.gold,
+            self.wood - other.wood,
+            self.stone - other.stone,
+        )
+    }
+}
+
+impl ops::Mul<i32> for &Resources {
+    type Output = Resources;
+
+    fn mul(self, times: i32) -> Resources {
+        Resources::new(self.gold * times, self.wood * times, self.stone * times)
+    }
+}
+
+impl ops::Div<i32> for &Resources {
+    type Output = Resources;
+
+    fn div(self, divisor: i32) -> Resources {
+        Resources::new(
+            self.gold / divisor,
+            self.wood / divisor,
+            self.stone / divisor,
+        )
+    }
+}
+
+// Resources are compared kind by kind: a >= b only if a has at least as much of every kind.
+// When one has more of one kind and less of another they are not comparable.
+impl PartialOrd for Resources {
+    fn partial_cmp(&self, other: &Resources) -> Option<Ordering> {
+        let orderings = [
+            self.gold.cmp(&other.gold),
+            self.wood.cmp(&other.wood),
+            self.stone.cmp(&other.stone),
+        ];
+        if orderings
+            .iter()
+            .all(|ordering| *ordering == Ordering::Equal)
+        {
+            Some(Ordering::Equal)
+        } else if orderings
+            .iter()
+            .all(|ordering| *ordering != Ordering::Greater)
+        {
+            Some(Ordering::Less)
+        } else if orderings.iter().all(|ordering| *ordering != Ordering::Less) {
+            Some(Ordering::Greater)
+        } else {
+            None
+        }
     }
 }
@@ -0,0 +1,10 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+// ChaCha8 is used instead of rand's StdRng because its output is guaranteed to stay the same
+// across platforms and rand versions, which is what makes seeded games reproducible
+pub type GameRng = ChaCha8Rng;
+
+pub fn new_rng(seed: u64) -> GameRng {
+    GameRng::seed_from_u64(seed)
+}
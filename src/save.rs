@@ -0,0 +1,76 @@
+use crate::building::Building;
+use crate::building_registry::BuildingRegistry;
+use crate::command::{Command, RecordedCommand};
+use crate::entity::{Entity, SavedEntityAction};
+use crate::entity_registry::EntityRegistry;
+use crate::ground::Ground;
+use crate::handle::HandleStore;
+use crate::path_finder::Path;
+use crate::projectile_handler::ProjectileHandler;
+use crate::rng::GameRng;
+use crate::team::Team;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::fs;
+use std::io;
+use std::rc::Rc;
+
+// Paths are shared by all the entities that got the same command, so they are saved only once
+// and entities refer to them by index
+#[derive(Default)]
+pub struct PathTable {
+    paths: Vec<Rc<RefCell<Path>>>,
+}
+
+impl PathTable {
+    pub fn index_of(&mut self, path: &Rc<RefCell<Path>>) -> usize {
+        if let Some(index) = self.paths.iter().position(|p| Rc::ptr_eq(p, path)) {
+            return index;
+        }
+        self.paths.push(path.clone());
+        self.paths.len() - 1
+    }
+
+    pub fn into_paths(self) -> Vec<Rc<RefCell<Path>>> {
+        self.paths
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct SavedEntity {
+    pub entity: Rc<RefCell<Entity>>,
+    pub action: SavedEntityAction,
+}
+
+// The whole simulation state, see Game::to_saved and Game::from_saved
+#[derive(Serialize, Deserialize)]
+pub struct SavedGame {
+    pub seed: u64,
+    pub entity_registry: EntityRegistry,
+    pub building_registry: BuildingRegistry,
+    pub tick: u32,
+    pub rng: GameRng,
+    pub command_queue: Vec<Command>,
+    pub command_log: Vec<RecordedCommand>,
+    pub ground: Ground,
+    pub teams: Vec<Team>,
+    pub paths: Vec<Rc<RefCell<Path>>>,
+    pub buildings: Vec<Rc<RefCell<Building>>>,
+    pub building_handles: HandleStore<Rc<RefCell<Building>>>,
+    pub entities: Vec<SavedEntity>,
+    pub entity_handles: HandleStore<Rc<RefCell<Entity>>>,
+    pub projectile_handler: ProjectileHandler,
+}
+
+impl SavedGame {
+    pub fn save(&self, path: &str) -> io::Result<()> {
+        let text =
+            ron::ser::to_string(self).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+        fs::write(path, text)
+    }
+
+    pub fn load(path: &str) -> io::Result<SavedGame> {
+        let text = fs::read_to_string(path)?;
+        ron::from_str(&text).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
@@ -1,4 +1,3 @@
-use crate::entity::EntityFilter;
 use crate::vec::Vec2f;
 use std::cell::RefCell;
 use std::collections::HashMap;
@@ -48,7 +47,7 @@ where
         )
     }
 
-    pub fn update_partition(&mut self, objects: &Vec<Rc<RefCell<T>>>) {
+    pub fn update_partition(&mut self, objects: &[Rc<RefCell<T>>]) {
         self.objects_by_area.clear();
         for object_rc in objects.iter() {
             let object = object_rc.borrow();
@@ -61,9 +60,8 @@ where
                     area_slot.push(object_rc.clone());
                 }
                 None => {
-                    let mut new_vec = Vec::new();
-                    new_vec.push(object_rc.clone());
-                    self.objects_by_area.insert(entity_area, new_vec);
+                    self.objects_by_area
+                        .insert(entity_area, vec![object_rc.clone()]);
                 }
             }
         }
@@ -97,42 +95,39 @@ where
 
         for x in min_x..max_x + 1 {
             for y in min_y..max_y + 1 {
-                match self.objects_by_area.get(&(x, y)) {
-                    Some(entities) => {
-                        for object_rc in entities.iter() {
-                            let object = object_rc.borrow();
-                            let object_position = object.get_position();
-
-                            if !object.matches_filter(&filter) {
-                                continue;
-                            }
+                if let Some(entities) = self.objects_by_area.get(&(x, y)) {
+                    for object_rc in entities.iter() {
+                        let object = object_rc.borrow();
+                        let object_position = object.get_position();
+
+                        if !object.matches_filter(&filter) {
+                            continue;
+                        }
 
-                            match &objects_in {
-                                ObjectFilter::InBox {
-                                    top_left,
-                                    bottom_right,
-                                } => {
-                                    if object_position.x >= top_left.x
-                                        && object_position.x <= bottom_right.x
-                                        && object_position.y >= top_left.y
-                                        && object_position.y <= bottom_right.y
-                                    {
-                                        objects_in_radius.push(object_rc.clone());
-                                    }
+                        match &objects_in {
+                            ObjectFilter::InBox {
+                                top_left,
+                                bottom_right,
+                            } => {
+                                if object_position.x >= top_left.x
+                                    && object_position.x <= bottom_right.x
+                                    && object_position.y >= top_left.y
+                                    && object_position.y <= bottom_right.y
+                                {
+                                    objects_in_radius.push(object_rc.clone());
                                 }
-                                ObjectFilter::InRadius {
-                                    position,
-                                    max_radius,
-                                } => {
-                                    let distance = (object_position - position.clone()).length();
-                                    if distance < *max_radius {
-                                        objects_in_radius.push(object_rc.clone());
-                                    }
+                            }
+                            ObjectFilter::InRadius {
+                                position,
+                                max_radius,
+                            } => {
+                                let distance = (object_position - position.clone()).length();
+                                if distance < *max_radius {
+                                    objects_in_radius.push(object_rc.clone());
                                 }
-                            };
-                        }
+                            }
+                        };
                     }
-                    None => {}
                 }
             }
         }
@@ -1,15 +1,24 @@
 use crate::resources::Resources;
+use crate::visibility::VisibilityGrid;
+use serde::{Deserialize, Serialize};
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Team {
     resources: Resources,
     team_id: u8,
+    supply_used: i32, // Units alive and in spawn queues
+    supply_cap: i32,  // Provided by constructed buildings
+    visibility: VisibilityGrid,
 }
 
 impl Team {
     pub fn new(team_id: u8) -> Team {
         Team {
-            resources: Resources::new(1000),
+            resources: Resources::new(1000, 500, 300),
             team_id,
+            supply_used: 0,
+            supply_cap: 0,
+            visibility: VisibilityGrid::new(),
         }
     }
 
@@ -28,4 +37,34 @@ impl Team {
     pub fn decrement_resources(&mut self, amount: &Resources) {
         self.resources.decrement(amount);
     }
+
+    pub fn get_supply_used(&self) -> i32 {
+        self.supply_used
+    }
+
+    pub fn get_supply_cap(&self) -> i32 {
+        self.supply_cap
+    }
+
+    pub fn set_supply(&mut self, supply_used: i32, supply_cap: i32) {
+        self.supply_used = supply_used;
+        self.supply_cap = supply_cap;
+    }
+
+    pub fn has_supply_for(&self, supply: i32) -> bool {
+        self.supply_used + supply <= self.supply_cap
+    }
+
+    // More supply is used than there is, e.g. after losing a farm
+    pub fn is_supply_blocked(&self) -> bool {
+        self.supply_used > self.supply_cap
+    }
+
+    pub fn get_visibility(&self) -> &VisibilityGrid {
+        &self.visibility
+    }
+
+    pub fn get_visibility_mut(&mut self) -> &mut VisibilityGrid {
+        &mut self.visibility
+    }
 }
@@ -1,7 +1,8 @@
+use serde::{Deserialize, Serialize};
 use std::hash::Hash;
 use std::ops;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vec2f {
     pub x: f32,
     pub y: f32,
@@ -9,7 +10,7 @@ pub struct Vec2f {
 
 impl Vec2f {
     pub fn new(x: f32, y: f32) -> Vec2f {
-        Vec2f { x: x, y: y }
+        Vec2f { x, y }
     }
 
     pub fn length(&self) -> f32 {
@@ -27,6 +28,19 @@ impl Vec2f {
     pub fn as_vec2i(&self) -> Vec2i {
         Vec2i::new(self.x as i32, self.y as i32)
     }
+
+    // Shortest distance from this point to the line segment between start and end
+    pub fn distance_to_segment(&self, start: &Vec2f, end: &Vec2f) -> f32 {
+        let segment = end.clone() - start.clone();
+        let length_squared = segment.x * segment.x + segment.y * segment.y;
+        if length_squared == 0.0 {
+            return (self.clone() - start.clone()).length();
+        }
+        let to_point = self.clone() - start.clone();
+        let t =
+            ((to_point.x * segment.x + to_point.y * segment.y) / length_squared).clamp(0.0, 1.0);
+        (self.clone() - (start.clone() + segment * t)).length()
+    }
 }
 
 impl ops::Add for Vec2f {
@@ -37,7 +51,7 @@ impl ops::Add for Vec2f {
     }
 }
 
-impl<'a, 'b> ops::Add<&'b Vec2f> for &'a Vec2f {
+impl<'b> ops::Add<&'b Vec2f> for &Vec2f {
     type Output = Vec2f;
 
     fn add(self, other: &'b Vec2f) -> Vec2f {
@@ -83,7 +97,7 @@ impl ops::Div<f32> for Vec2f {
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Vec2i {
     pub x: i32,
     pub y: i32,
@@ -100,15 +114,12 @@ impl PartialEq for Vec2i {
     fn eq(&self, other: &Vec2i) -> bool {
         self.x == other.x && self.y == other.y
     }
-    fn ne(&self, other: &Vec2i) -> bool {
-        self.x != other.x || self.y != other.y
-    }
 }
 impl Eq for Vec2i {}
 
 impl Vec2i {
     pub fn new(x: i32, y: i32) -> Vec2i {
-        Vec2i { x: x, y: y }
+        Vec2i { x, y }
     }
 
     // pub fn length(&self) -> i32 {
@@ -136,7 +147,7 @@ impl ops::Add for Vec2i {
     }
 }
 
-impl<'a, 'b> ops::Add<&'b Vec2i> for &'a Vec2i {
+impl<'b> ops::Add<&'b Vec2i> for &Vec2i {
     type Output = Vec2i;
 
     fn add(self, other: &'b Vec2i) -> Vec2i {
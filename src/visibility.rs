@@ -0,0 +1,178 @@
+use crate::building::Building;
+use crate::building_registry::BuildingType;
+use crate::constants::{GROUND_HEIGHT, GROUND_WIDTH};
+use crate::ground::{Ground, GroundType};
+use crate::handle::Handle;
+use crate::vec::{Vec2f, Vec2i};
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Visibility {
+    Hidden,   // Never seen
+    Explored, // Seen before, but nothing of the team sees it now
+    Visible,
+}
+
+// An enemy building as the team last saw it
+#[derive(Clone, Serialize, Deserialize)]
+pub struct BuildingSnapshot {
+    pub building_id: Handle,
+    pub building_type: BuildingType,
+    pub position: Vec2i,
+    pub width: i32,
+    pub height: i32,
+    pub team: u8,
+    pub health: i32,
+    pub full_health: i32, // Health of the finished building
+    pub is_constructed: bool,
+}
+
+impl BuildingSnapshot {
+    pub fn new(building: &Building) -> BuildingSnapshot {
+        BuildingSnapshot {
+            building_id: building.get_id(),
+            building_type: building.get_building_type(),
+            position: building.get_position(),
+            width: building.get_width(),
+            height: building.get_height(),
+            team: building.get_team(),
+            health: building.health.get_health(),
+            full_health: building.get_definition().health,
+            is_constructed: building.is_constructed(),
+        }
+    }
+
+    pub fn get_size(&self) -> Vec2i {
+        Vec2i::new(self.width, self.height)
+    }
+}
+
+// What a team sees of the ground, updated every tick from the sight of its units and buildings
+#[derive(Clone, Serialize, Deserialize)]
+pub struct VisibilityGrid {
+    tiles: Vec<Visibility>,
+    width: i32,
+    height: i32,
+    remembered_buildings: Vec<BuildingSnapshot>, // Enemy buildings as they were last seen
+    remembered_ground: Vec<GroundType>, // e.g. resources that have run out since they were seen
+}
+
+impl Default for VisibilityGrid {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VisibilityGrid {
+    pub fn new() -> VisibilityGrid {
+        VisibilityGrid {
+            tiles: vec![Visibility::Hidden; (GROUND_WIDTH * GROUND_HEIGHT) as usize],
+            width: GROUND_WIDTH,
+            height: GROUND_HEIGHT,
+            remembered_buildings: Vec::new(),
+            remembered_ground: vec![GroundType::Empty; (GROUND_WIDTH * GROUND_HEIGHT) as usize],
+        }
+    }
+
+    pub fn get_at(&self, x: i32, y: i32) -> Visibility {
+        if x < 0 || x >= self.width || y < 0 || y >= self.height {
+            return Visibility::Hidden;
+        }
+        self.tiles[(y * self.width + x) as usize]
+    }
+
+    pub fn is_visible(&self, position: &Vec2f) -> bool {
+        self.get_at(position.x as i32, position.y as i32) == Visibility::Visible
+    }
+
+    // Whether any tile of the area is visible, e.g. of a building
+    pub fn is_area_visible(&self, top_left: &Vec2i, size: &Vec2i) -> bool {
+        (top_left.y..top_left.y + size.y).any(|y| {
+            (top_left.x..top_left.x + size.x).any(|x| self.get_at(x, y) == Visibility::Visible)
+        })
+    }
+
+    // Everything that was visible is only explored until revealed again
+    pub fn start_update(&mut self) {
+        for tile in self.tiles.iter_mut() {
+            if *tile == Visibility::Visible {
+                *tile = Visibility::Explored;
+            }
+        }
+    }
+
+    // Makes the tiles within `radius` of the area visible when there is a line of sight to them.
+    // The area is a single point for units. For buildings it spans the centers of their tiles,
+    // so that they see from their edges instead of through their own tiles.
+    pub fn reveal(&mut self, ground: &Ground, top_left: &Vec2f, bottom_right: &Vec2f, radius: f32) {
+        let min_x = 0.max((top_left.x - radius) as i32);
+        let max_x = (self.width - 1).min((bottom_right.x + radius) as i32);
+        let min_y = 0.max((top_left.y - radius) as i32);
+        let max_y = (self.height - 1).min((bottom_right.y + radius) as i32);
+        for y in min_y..=max_y {
+            for x in min_x..=max_x {
+                let index = (y * self.width + x) as usize;
+                if self.tiles[index] == Visibility::Visible {
+                    continue;
+                }
+                let tile_center = Vec2f::new(x as f32 + 0.5, y as f32 + 0.5);
+                let from = Vec2f::new(
+                    tile_center.x.clamp(top_left.x, bottom_right.x),
+                    tile_center.y.clamp(top_left.y, bottom_right.y),
+                );
+                if (tile_center.clone() - from.clone()).length() <= radius
+                    && ground.has_line_of_sight(&from, &tile_center)
+                {
+                    self.tiles[index] = Visibility::Visible;
+                }
+            }
+        }
+    }
+
+    // The ground as the team knows it: as it is where the team sees, as it was last seen elsewhere
+    pub fn get_known_ground_at(&self, ground: &Ground, x: i32, y: i32) -> GroundType {
+        match self.get_at(x, y) {
+            Visibility::Visible => ground.get_at(x, y),
+            Visibility::Explored => self.remembered_ground[(y * self.width + x) as usize].clone(),
+            Visibility::Hidden => GroundType::Empty,
+        }
+    }
+
+    pub fn remember_ground(&mut self, ground: &Ground) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let index = (y * self.width + x) as usize;
+                if self.tiles[index] == Visibility::Visible {
+                    self.remembered_ground[index] = ground.get_at(x, y);
+                }
+            }
+        }
+    }
+
+    // Includes the buildings that are visible right now, as they are seen this tick
+    pub fn get_remembered_buildings(&self) -> &[BuildingSnapshot] {
+        &self.remembered_buildings
+    }
+
+    // Forgets what was remembered where the team sees again, since the building has been
+    // destroyed or is snapshotted anew, then snapshots the visible enemy buildings
+    pub fn remember_buildings(&mut self, buildings: &[Rc<RefCell<Building>>], team_id: u8) {
+        let mut remembered_buildings = std::mem::take(&mut self.remembered_buildings);
+        remembered_buildings
+            .retain(|snapshot| !self.is_area_visible(&snapshot.position, &snapshot.get_size()));
+        for building in buildings.iter() {
+            let building = building.borrow();
+            if building.get_team() != team_id
+                && self.is_area_visible(
+                    &building.get_position(),
+                    &Vec2i::new(building.get_width(), building.get_height()),
+                )
+            {
+                remembered_buildings.push(BuildingSnapshot::new(&building));
+            }
+        }
+        self.remembered_buildings = remembered_buildings;
+    }
+}
@@ -0,0 +1,51 @@
+use rts2::building_registry::BuildingRegistry;
+use rts2::command::Command;
+use rts2::entity_registry::{EntityRegistry, EntityType};
+use rts2::game::Game;
+use rts2::handle::Handle;
+use rts2::vec::Vec2f;
+
+const TICKS: u32 = 300;
+
+fn team_unit_ids(game: &Game, team: u8) -> Vec<Handle> {
+    game.get_entity_container()
+        .iter_alive()
+        .filter(|entity| entity.borrow().get_team() == team && !entity.borrow().is_worker())
+        .map(|entity| entity.borrow().get_id())
+        .collect()
+}
+
+// Both teams send their units towards each other and queue workers, so that movement, combat
+// and spawning all play a part
+fn run(seed: u64) -> Vec<u64> {
+    let mut game = Game::new(seed, EntityRegistry::default(), BuildingRegistry::default());
+    let mut checksums = Vec::new();
+    for tick in 0..TICKS {
+        if tick % 100 == 10 {
+            let command = game.command_at(team_unit_ids(&game, 0), &Vec2f::new(60.0, 45.0), true);
+            game.queue_command(command);
+            let command = game.command_at(team_unit_ids(&game, 1), &Vec2f::new(20.0, 15.0), true);
+            game.queue_command(command);
+            let building_id = game.get_building_container().get_buildings()[0]
+                .borrow()
+                .get_id();
+            game.queue_command(Command::Spawn {
+                building_id,
+                entity_type: EntityType::new("worker"),
+            });
+        }
+        game.update();
+        checksums.push(game.state_checksum());
+    }
+    checksums
+}
+
+#[test]
+fn same_seed_and_commands_give_identical_runs() {
+    assert_eq!(run(7), run(7));
+}
+
+#[test]
+fn different_seeds_give_different_runs() {
+    assert_ne!(run(7).last(), run(8).last());
+}
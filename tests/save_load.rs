@@ -0,0 +1,96 @@
+use rts2::building_registry::BuildingRegistry;
+use rts2::command::Command;
+use rts2::entity_registry::{EntityRegistry, EntityType};
+use rts2::game::Game;
+use rts2::handle::Handle;
+use rts2::vec::Vec2f;
+
+fn team_unit_ids(game: &Game, team: u8) -> Vec<Handle> {
+    game.get_entity_container()
+        .iter_alive()
+        .filter(|entity| entity.borrow().get_team() == team && !entity.borrow().is_worker())
+        .map(|entity| entity.borrow().get_id())
+        .collect()
+}
+
+fn entity_count(game: &Game) -> usize {
+    game.get_entity_container().iter_alive().count()
+}
+
+// Both armies attack-move to the middle of the map and fight until some units have died
+fn game_after_deaths() -> Game {
+    let mut game = Game::new(7, EntityRegistry::default(), BuildingRegistry::default());
+    let start_count = entity_count(&game);
+    for team in 0..2 {
+        let command = game.command_at(team_unit_ids(&game, team), &Vec2f::new(40.0, 30.0), true);
+        game.queue_command(command);
+    }
+    for _ in 0..300 {
+        game.update();
+    }
+    assert!(entity_count(&game) < start_count);
+    game
+}
+
+fn save_and_load(game: &Game, name: &str) -> Game {
+    let path = std::env::temp_dir().join(format!("rts2_{}_{}.ron", name, std::process::id()));
+    let path = path.to_str().unwrap();
+    game.save(path).unwrap();
+    let loaded = Game::load(path).unwrap();
+    std::fs::remove_file(path).unwrap();
+    loaded
+}
+
+#[test]
+fn loaded_game_continues_like_the_saved_one() {
+    let mut game = game_after_deaths();
+    let mut loaded = save_and_load(&game, "continue");
+    assert_eq!(loaded.state_checksum(), game.state_checksum());
+
+    for _ in 0..100 {
+        game.update();
+        loaded.update();
+        assert_eq!(loaded.state_checksum(), game.state_checksum());
+    }
+}
+
+#[test]
+fn units_can_be_spawned_after_loading() {
+    let game = game_after_deaths();
+    let mut loaded = save_and_load(&game, "spawn");
+    let ids_after_load: Vec<Handle> = loaded
+        .get_entity_container()
+        .iter_alive()
+        .map(|entity| entity.borrow().get_id())
+        .collect();
+
+    let building_id = loaded.get_building_container().get_buildings()[0]
+        .borrow()
+        .get_id();
+    loaded.queue_command(Command::Spawn {
+        building_id,
+        entity_type: EntityType::new("worker"),
+    });
+    // Units keep dying in the fight, so the new one is found by its id
+    for _ in 0..200 {
+        loaded.update();
+        let spawned = loaded
+            .get_entity_container()
+            .iter_alive()
+            .any(|entity| !ids_after_load.contains(&entity.borrow().get_id()));
+        if spawned {
+            return;
+        }
+    }
+    panic!("No unit was spawned after loading");
+}
+
+#[test]
+fn save_with_missing_paths_is_rejected() {
+    let game = game_after_deaths();
+    let mut saved = game.to_saved();
+    assert!(!saved.paths.is_empty());
+    saved.paths.clear();
+    let error = Game::from_saved(saved).err().unwrap();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+}